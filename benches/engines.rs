@@ -0,0 +1,33 @@
+//! Benchmark of the syn based tree engine over the test fixtures.
+//!
+//! The graph engine (`calculator::calculate`) has no Rust front end yet, so
+//! it can't be run on the same inputs; once a `calculator::Parser` for Rust
+//! exists it belongs next to `tree` here so the two can be compared.
+use criterion::{criterion_group, criterion_main, Criterion};
+use cyclomatic_complexity::parsers::rust_parser::ComplexityTree;
+
+const FIXTURES: &[&str] = &[
+    "tests/fixtures/clean.rs",
+    "tests/fixtures/over_budget.rs",
+    "tests/fixtures/scan/good.rs",
+];
+
+fn tree(c: &mut Criterion) {
+    println!("{:<32} complexity", "fixture");
+    for fixture in FIXTURES {
+        let tree = ComplexityTree::generate(fixture.to_string()).unwrap();
+        let total: usize = tree.functions().map(|(_, node)| node.complexity).sum();
+        println!("{:<32} {}", fixture, total);
+    }
+
+    let mut group = c.benchmark_group("tree");
+    for fixture in FIXTURES {
+        group.bench_function(*fixture, |b| {
+            b.iter(|| ComplexityTree::generate(fixture.to_string()).unwrap())
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tree);
+criterion_main!(benches);
@@ -0,0 +1,242 @@
+//! Module holding a configured analysis that can be run repeatedly.
+use crate::cache::FunctionCache;
+use crate::parsers::delphi;
+use crate::parsers::error::{ParseError, ParseErrorKind, ParseResult};
+use crate::parsers::rust_parser::{ComplexityConfig, ComplexityTree, Weights};
+
+use std::fs;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Language to analyze files as.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Lang {
+    Rust,
+    Delphi,
+    /// Pick by extension, see `Lang::detect`.
+    Auto,
+}
+
+impl Lang {
+    /// `.rs` is Rust and `.pas`, `.dpr` and `.inc` are Delphi, regardless of
+    /// case.
+    pub fn detect(path: &str) -> Option<Lang> {
+        let ext = Path::new(path).extension()?.to_str()?.to_ascii_lowercase();
+        match ext.as_str() {
+            "rs" => Some(Lang::Rust),
+            "pas" | "dpr" | "inc" => Some(Lang::Delphi),
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Lang {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Lang, String> {
+        match s {
+            "rust" => Ok(Lang::Rust),
+            "delphi" => Ok(Lang::Delphi),
+            "auto" => Ok(Lang::Auto),
+            other => Err(format!("unknown language: {}", other)),
+        }
+    }
+}
+
+/// Options controlling how a single file is analyzed.
+///
+/// Like the trees it produces, this must stay `Send + Sync` so files can be
+/// analyzed on a thread pool; a test enforces it.
+#[derive(Debug, Clone)]
+pub struct AnalyzeOptions {
+    pub weights: Weights,
+    /// Which constructs count as decisions.
+    pub counted: ComplexityConfig,
+    /// Analyze `#[automatically_derived]` impls, which are skipped by default.
+    pub include_derived: bool,
+    /// Analyze `#[cfg(test)]` items and `#[test]` functions, which are
+    /// skipped by default.
+    pub include_tests: bool,
+    /// Attribute the default methods an impl doesn't override to the impl,
+    /// for traits declared in the same file that the impl names by their
+    /// path in it; names brought in with `use` aren't followed.
+    pub resolve_trait_defaults: bool,
+    /// How deeply expressions may nest before the walk stops descending and
+    /// marks the function as truncated. Delphi statements nested deeper are
+    /// an error.
+    pub max_depth: usize,
+    /// The other options but for `max_depth` only apply to Rust.
+    pub lang: Lang,
+}
+
+/// Nesting depth hand written code never gets close to.
+pub const DEFAULT_MAX_DEPTH: usize = 512;
+
+impl Default for AnalyzeOptions {
+    fn default() -> AnalyzeOptions {
+        AnalyzeOptions {
+            weights: Weights::default(),
+            counted: ComplexityConfig::default(),
+            include_derived: false,
+            include_tests: false,
+            resolve_trait_defaults: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            lang: Lang::Auto,
+        }
+    }
+}
+
+/// Analyzes files with one fixed set of options.
+///
+/// Long running callers (e.g. watch mode) create one `Analyzer` up front and
+/// call it for every change instead of threading the options around.
+#[derive(Debug, Default, Clone)]
+pub struct Analyzer {
+    opts: AnalyzeOptions,
+}
+
+impl Analyzer {
+    pub fn new(opts: AnalyzeOptions) -> Analyzer {
+        Analyzer { opts }
+    }
+
+    pub fn options(&self) -> &AnalyzeOptions {
+        &self.opts
+    }
+
+    /// Read and analyze the file at `path`.
+    pub fn analyze(&self, path: &str) -> ParseResult<ComplexityTree> {
+        if self.lang(path)? == Lang::Delphi {
+            let src = fs::read_to_string(path)?;
+            return delphi::graph::tree(path.to_string(), &src, self.opts.max_depth);
+        }
+
+        ComplexityTree::generate_with_options(path.to_string(), &self.opts)
+    }
+
+    /// Analyze source that is already in memory, naming the root node `name`.
+    pub fn analyze_source(&self, name: &str, src: &str) -> ParseResult<ComplexityTree> {
+        if self.lang(name)? == Lang::Delphi {
+            return delphi::graph::tree(name.to_string(), src, self.opts.max_depth);
+        }
+
+        ComplexityTree::from_source_with_options(name.to_string(), src, &self.opts)
+    }
+
+    /// Like `analyze_source`, reusing the results of unchanged functions from
+    /// an earlier analysis by this analyzer.
+    pub fn analyze_source_cached(
+        &self,
+        name: &str,
+        src: &str,
+        cache: &mut FunctionCache,
+    ) -> ParseResult<ComplexityTree> {
+        if self.lang(name)? == Lang::Delphi {
+            return delphi::graph::tree(name.to_string(), src, self.opts.max_depth);
+        }
+
+        ComplexityTree::from_source_with_cache(name.to_string(), src, &self.opts, cache)
+    }
+
+    /// The language to analyze `path` as, which with `Lang::Auto` must be
+    /// told by its extension.
+    fn lang(&self, path: &str) -> ParseResult<Lang> {
+        match self.opts.lang {
+            Lang::Auto => Lang::detect(path).ok_or_else(|| {
+                ParseError::kind(ParseErrorKind::UnknownLanguage).msg(String::from(
+                    "unknown extension, pass --lang rust or --lang delphi",
+                ))
+            }),
+            lang => Ok(lang),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AnalyzeOptions, Analyzer, Lang};
+    use crate::parsers::error::ParseErrorKind;
+    use crate::parsers::rust_parser::{ComplexityNode, ComplexityTree, Weights};
+    use rstest::rstest;
+
+    fn _assert_send_sync<T: Send + Sync>() {}
+
+    #[test]
+    fn parallel_scan_types_are_send_and_sync() {
+        _assert_send_sync::<ComplexityTree>();
+        _assert_send_sync::<ComplexityNode>();
+        _assert_send_sync::<AnalyzeOptions>();
+        _assert_send_sync::<Analyzer>();
+    }
+
+    #[test]
+    fn delphi_files_are_picked_by_extension() {
+        let analyzer = Analyzer::default();
+
+        let tree = analyzer.analyze("tests/fixtures/delphi/unit.pas").unwrap();
+        let routines: Vec<(&str, usize)> = tree
+            .root
+            .children
+            .iter()
+            .map(|node| (node.name.as_str(), node.complexity))
+            .collect();
+        assert_eq!(vec![("Sign", 1), ("Count", 1)], routines);
+
+        let src = "procedure Foo; begin if a then b; end;";
+        assert_eq!(
+            1,
+            analyzer
+                .analyze_source("FOO.DPR", src)
+                .unwrap()
+                .root
+                .children
+                .len()
+        );
+        assert!(analyzer.analyze_source("foo.rs", src).is_err());
+    }
+
+    #[rstest]
+    #[case("src/lib.rs", Some(Lang::Rust))]
+    #[case("Unit.PAS", Some(Lang::Delphi))]
+    #[case("project.dpr", Some(Lang::Delphi))]
+    #[case("defines.inc", Some(Lang::Delphi))]
+    #[case("notes.txt", None)]
+    #[case("Makefile", None)]
+    fn languages_are_detected_by_extension(#[case] path: &str, #[case] expected: Option<Lang>) {
+        assert_eq!(expected, Lang::detect(path));
+    }
+
+    #[test]
+    fn lang_overrides_the_extension() {
+        let src = "procedure Foo; begin if a then b; end;";
+        let auto = Analyzer::default().analyze_source("foo.txt", src);
+        assert_eq!(ParseErrorKind::UnknownLanguage, auto.unwrap_err().kind);
+
+        let delphi = Analyzer::new(AnalyzeOptions {
+            lang: Lang::Delphi,
+            ..AnalyzeOptions::default()
+        });
+        let tree = delphi.analyze_source("foo.txt", src).unwrap();
+        assert_eq!(1, tree.root.children[0].complexity);
+    }
+
+    #[test]
+    fn repeated_calls_give_identical_results() {
+        let analyzer = Analyzer::new(AnalyzeOptions {
+            weights: Weights { if_else: 1 },
+            ..AnalyzeOptions::default()
+        });
+        let src = "fn f() { if a {} else {} } impl Foo { fn g(&self) { match x { _ => {} } } }";
+
+        let first = analyzer.analyze_source("lib.rs", src).unwrap();
+        let second = analyzer.analyze_source("lib.rs", src).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(2, first.root.children[0].complexity);
+
+        let from_file = analyzer.analyze("tests/fixtures/clean.rs").unwrap();
+        assert_eq!(
+            from_file,
+            analyzer.analyze("tests/fixtures/clean.rs").unwrap()
+        );
+    }
+}
@@ -1,61 +1,344 @@
-use cyclomatic_complexity::config::Config;
+use cyclomatic_complexity::analyzer::{AnalyzeOptions, Analyzer, Lang};
+use cyclomatic_complexity::calculator::Parser;
 use cyclomatic_complexity::config::ConfigResult;
+use cyclomatic_complexity::config::{Color, Config, Verbosity};
+use cyclomatic_complexity::diff;
+use cyclomatic_complexity::discovery::{self, ScanOptions};
+use cyclomatic_complexity::git;
+use cyclomatic_complexity::parsers::delphi::graph::{self, DelphiGraphParser};
 use cyclomatic_complexity::parsers::rust_parser::ComplexityNode;
 use cyclomatic_complexity::parsers::rust_parser::ComplexityTree;
+use cyclomatic_complexity::parsers::rust_parser::{ComplexityConfig, Metric, Weights};
+use cyclomatic_complexity::report::text::{self, TextOptions};
+#[cfg(feature = "serde")]
+use cyclomatic_complexity::report::{json, ndjson, review, sarif};
+use cyclomatic_complexity::report::{markdown, plain_paths, tabular, Format, DEFAULT_BUDGET};
+use cyclomatic_complexity::{ParseError, ParseErrorKind};
 
 use std::env;
+use std::fs;
+use std::io::{self, IsTerminal, Read};
+use std::process;
+use std::time::Instant;
+
+/// Name of the tree of the source read from stdin.
+const STDIN: &str = "<stdin>";
 
 fn main() {
-    let config: ConfigResult<Config> = Config::parse(env::args());
-    if config.is_err() {
-        println!("{}", config.err().unwrap().message);
-        return;
-    } else {
-        if 1 % 2 == 0 {
-        } else if 2 % 2 == 0 {
+    let config: ConfigResult<Config> = Config::load(env::args());
+    // help and version go to stdout and exit 0, errors to stderr and exit 1
+    if let Err(err) = &config {
+        err.exit();
+    }
+    let config: Config = config.ok().unwrap();
+    if config.summary && !matches!(config.format, Format::Text | Format::Json) {
+        eprintln!("error: --summary needs --format text or json");
+        process::exit(1);
+    }
+    let opts = ScanOptions {
+        analyze: AnalyzeOptions {
+            weights: Weights {
+                if_else: config.if_else_weight,
+            },
+            counted: ComplexityConfig {
+                count_boolean_operators: config.boolean_ops,
+                count_match_arms: config.match_arms,
+                count_try: config.try_ops,
+            },
+            include_derived: config.include_derived,
+            include_tests: config.include_tests,
+            resolve_trait_defaults: config.resolve_trait_defaults,
+            max_depth: config.max_depth,
+            lang: config.lang,
+        },
+        sample: config.sample,
+        seed: config.seed,
+        dirs: config.dirs.clone(),
+        exclude: config.exclude.clone(),
+    };
+
+    let text_opts = TextOptions {
+        align: config.align,
+        sort: config.sort,
+        line_numbers: config.line_numbers,
+        min_complexity: config.min_complexity,
+        color: Some(config.color_thresholds).filter(|_| colored(config.color)),
+    };
+
+    let analyzer = Analyzer::new(opts.analyze.clone());
+    let mut results = vec![];
+    for file in discovery::scan(&config.files, &opts) {
+        let started = Instant::now();
+        let tree = analyzer.analyze(&file);
+        results.push((file, tree, started.elapsed()));
+    }
+    if config.stdin {
+        let mut src = String::new();
+        // stdin has no extension to go by
+        let analyze = match config.lang {
+            Lang::Auto => AnalyzeOptions {
+                lang: Lang::Rust,
+                ..opts.analyze.clone()
+            },
+            _ => opts.analyze.clone(),
+        };
+        let started = Instant::now();
+        let tree = match io::stdin().read_to_string(&mut src) {
+            Ok(_) => Analyzer::new(analyze).analyze_source(STDIN, &src),
+            Err(err) => Err(err.into()),
+        };
+        results.push((String::from(STDIN), tree, started.elapsed()));
+    }
+
+    let mut failed = false;
+    let mut unreadable = false;
+    let mut trees: Vec<ComplexityTree> = vec![];
+    for (file, tree, elapsed) in results {
+        match tree {
+            Ok(mut tree) => {
+                if config.verbosity == Verbosity::Verbose {
+                    eprintln!(
+                        "{}: {} nodes, {} functions in {:.2?}",
+                        file,
+                        tree.nodes().count(),
+                        tree.functions().count(),
+                        elapsed
+                    );
+                }
+                tree.use_metric(config.metric);
+                if config.only_public {
+                    tree.hide_private();
+                }
+                let truncated = tree.functions().filter(|(_, node)| node.truncated);
+                for (path, _) in truncated {
+                    if config.verbosity != Verbosity::Quiet {
+                        eprintln!(
+                            "warning: {}: [{}] is nested deeper than --max-depth; its complexity is a lower bound",
+                            file, path
+                        );
+                    }
+                }
+                if let Some(threshold) = config.threshold {
+                    failed |= exceeds(&tree.root, threshold);
+                }
+                trees.push(tree);
+            }
+            Err(err) => {
+                eprintln!("{}", describe(&file, &err));
+                unreadable = true;
+            }
+        }
+    }
+    // a partial report would pass for a complete one
+    if unreadable {
+        process::exit(2);
+    }
+
+    if config.debug_graph {
+        for tree in trees
+            .iter()
+            .filter(|tree| is_delphi(&tree.root.name, config.lang))
+        {
+            debug_graph(&tree.root.name, config.max_depth);
         }
     }
-    if 1 % 2 == 0 {
-        if 2 % 2 == 0 {
-        } else if 2 % 3 == 0 {
-        } else {
+
+    match config.format {
+        Format::Text => {
+            for tree in trees.iter() {
+                print!("{}", text::render(tree, &text_opts));
+            }
+            if config.summary {
+                print!("{}", text::summary(&trees));
+            }
+        }
+        Format::Csv => print!("{}", tabular::csv(&trees, &config.columns)),
+        Format::Tsv => print!("{}", tabular::tsv(&trees, &config.columns)),
+        #[cfg(feature = "serde")]
+        Format::Ndjson => print!("{}", ndjson::render(&trees)),
+        #[cfg(feature = "serde")]
+        Format::Json if config.summary => print!("{}", json::summary(&trees)),
+        #[cfg(feature = "serde")]
+        Format::Json => print!("{}", json::render(&trees)),
+        #[cfg(feature = "serde")]
+        Format::Review => {
+            let budget = config.threshold.unwrap_or(DEFAULT_BUDGET);
+            print!("{}", review::render(&trees, budget));
+        }
+        #[cfg(feature = "serde")]
+        Format::Sarif => {
+            let budget = config.threshold.unwrap_or(DEFAULT_BUDGET);
+            print!("{}", sarif::render(&trees, budget));
+        }
+        #[cfg(not(feature = "serde"))]
+        Format::Ndjson | Format::Json | Format::Review | Format::Sarif => {
+            eprintln!("error: JSON output needs a build with the serde feature");
+            process::exit(1);
         }
-    } else if 2 % 4 == 0 {
-        if 2 % 5 == 0 {
-        } else if 2 % 6 == 0 {
+        Format::PlainPaths => {
+            let budget = config.threshold.unwrap_or(DEFAULT_BUDGET);
+            print!("{}", plain_paths::render(&trees, budget));
         }
-    } else {
-        if 2 % 7 == 0 {
-        } else {
+        Format::Markdown => print!("{}", markdown::render(&trees, config.min_complexity)),
+        Format::Dot => {
+            for tree in trees.iter() {
+                let file = &tree.root.name;
+                if !is_delphi(file, config.lang) {
+                    eprintln!("error: {}: --format dot needs a Delphi file", file);
+                    failed = true;
+                    continue;
+                }
+                match DelphiGraphParser.parse(file.clone()) {
+                    Ok(graph) => print!("{}", graph.to_dot()),
+                    Err(err) => {
+                        eprintln!("{}", describe(file, &err));
+                        failed = true;
+                    }
+                }
+            }
         }
     }
-    let config: Config = config.ok().unwrap();
-    display_complexity(config.file);
+
+    if config.fail_on_regression {
+        match previous_trees(&config.base, &trees, &opts, config.metric) {
+            Ok(previous) => {
+                // stdout is the report, which may be machine readable
+                for regression in diff::regressions(&previous, &trees) {
+                    eprintln!("Regression: {}", regression);
+                    failed = true;
+                }
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                failed = true;
+            }
+        }
+    }
+
+    if let Some(baseline) = &config.baseline {
+        let budget = config.threshold.unwrap_or(DEFAULT_BUDGET);
+        match baseline_trees(baseline) {
+            Ok(previous) => {
+                for regression in diff::regressions(&previous, &trees) {
+                    eprintln!("Regression: {}", regression);
+                    failed = true;
+                }
+                for addition in diff::additions(&previous, &trees, budget) {
+                    eprintln!("Regression: {}", addition);
+                    failed = true;
+                }
+            }
+            Err(err) => {
+                eprintln!("error: {}: {}", baseline, err);
+                failed = true;
+            }
+        }
+    }
+
+    if failed {
+        process::exit(1);
+    }
+}
+
+/// Read back the trees of a `--format json` report.
+#[cfg(feature = "serde")]
+fn baseline_trees(path: &str) -> Result<Vec<ComplexityTree>, String> {
+    let src = fs::read_to_string(path).map_err(|err| err.to_string())?;
+    json::parse(&src).map_err(|err| err.to_string())
+}
+
+#[cfg(not(feature = "serde"))]
+fn baseline_trees(_path: &str) -> Result<Vec<ComplexityTree>, String> {
+    Err(String::from(
+        "reading a baseline needs a build with the serde feature",
+    ))
+}
+
+/// Analyze the analyzed files as they were at the merge-base with `base`.
+/// Files that didn't exist there or no longer parse are skipped, and so is
+/// stdin, which has no history.
+fn previous_trees(
+    base: &str,
+    trees: &[ComplexityTree],
+    opts: &ScanOptions,
+    metric: Metric,
+) -> std::io::Result<Vec<ComplexityTree>> {
+    let rev = git::merge_base(base)?;
+    let analyzer = Analyzer::new(opts.analyze.clone());
+
+    let mut previous = vec![];
+    for tree in trees.iter().filter(|tree| tree.root.name != STDIN) {
+        let file = &tree.root.name;
+        if let Some(src) = git::show(&rev, file)? {
+            if let Ok(mut tree) = analyzer.analyze_source(file, &src) {
+                tree.use_metric(metric);
+                previous.push(tree);
+            }
+        }
+    }
+
+    Ok(previous)
 }
 
-fn display_complexity(file_path: String) {
-    let root = ComplexityTree::generate(file_path).ok().unwrap().root;
-    println!("File: {}", root.name);
-    for child in root.children {
-        display(&child, String::new());
+/// `error: could not parse <file>: <cause> at <line>:<column>` and the like.
+fn describe(file: &str, err: &ParseError) -> String {
+    let cause = err.msg.clone().unwrap_or_else(|| err.kind.to_string());
+    match (&err.kind, err.position) {
+        (ParseErrorKind::Io, _) => format!("error: could not read {}: {}", file, cause),
+        (ParseErrorKind::UnknownLanguage, _) => {
+            format!("error: could not analyze {}: {}", file, cause)
+        }
+        (_, Some((line, column))) => format!(
+            "error: could not parse {}: {} at {}:{}",
+            file, cause, line, column
+        ),
+        (_, None) => format!("error: could not parse {}: {}", file, cause),
     }
-    println!();
 }
 
-fn display(node: &ComplexityNode, path: String) {
-    let mut path_here: String = path;
-    if !path_here.is_empty() {
-        path_here += " > ";
+/// Whether `file` is a Delphi file that can be read again, unlike stdin.
+fn is_delphi(file: &str, lang: Lang) -> bool {
+    match lang {
+        Lang::Auto => Lang::detect(file) == Some(Lang::Delphi),
+        lang => lang == Lang::Delphi && file != STDIN,
     }
-    path_here += node.kind.to_string().as_str();
-    path_here += ": ";
-    path_here += node.name.as_str();
+}
 
-    if node.children.is_empty() {
-        println!("[{}] Complexity => {}", path_here, node.complexity);
-    } else {
-        for child in node.children.iter() {
-            display(child, path_here.clone());
+/// `debug: <file>: [Fn: <routine>] <E> - <N> + <P> + <X> = <complexity>` for
+/// every routine of `file`, to stderr.
+fn debug_graph(file: &str, max_depth: usize) {
+    let routines = fs::read_to_string(file)
+        .map_err(ParseError::from)
+        .and_then(|src| graph::routines(&src, max_depth));
+    match routines {
+        Ok(routines) => {
+            for routine in routines {
+                eprintln!(
+                    "debug: {}: [Fn: {}] {}",
+                    file,
+                    routine.name,
+                    routine.graph.explain()
+                );
+            }
         }
+        Err(err) => eprintln!("{}", describe(file, &err)),
     }
 }
+
+/// Whether `color` asks for colors, following https://no-color.org for
+/// `auto`.
+fn colored(color: Color) -> bool {
+    match color {
+        Color::Always => true,
+        Color::Never => false,
+        Color::Auto => {
+            io::stdout().is_terminal()
+                && env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+        }
+    }
+}
+
+fn exceeds(node: &ComplexityNode, threshold: usize) -> bool {
+    (!node.hidden && node.complexity > threshold)
+        || node.children.iter().any(|child| exceeds(child, threshold))
+}
@@ -0,0 +1,34 @@
+use cyclomatic_complexity::config::ConfigResult;
+use cyclomatic_complexity::config::TrendConfig;
+use cyclomatic_complexity::report::ndjson;
+use cyclomatic_complexity::report::trend;
+
+use std::env;
+use std::fs;
+use std::process;
+
+fn main() {
+    let config: ConfigResult<TrendConfig> = TrendConfig::parse(env::args());
+    // help and version go to stdout and exit 0, errors to stderr and exit 1
+    if let Err(err) = &config {
+        err.exit();
+    }
+    let config: TrendConfig = config.ok().unwrap();
+
+    let mut runs = vec![];
+    for (label, report) in config.reports {
+        let records = fs::read_to_string(&report)
+            .map_err(|err| err.to_string())
+            .and_then(|src| ndjson::parse(&src).map_err(|err| err.to_string()));
+
+        match records {
+            Ok(records) => runs.push((label, records)),
+            Err(err) => {
+                eprintln!("error: {}: {}", report, err);
+                process::exit(1);
+            }
+        }
+    }
+
+    print!("{}", trend::aggregate(&runs));
+}
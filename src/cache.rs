@@ -0,0 +1,216 @@
+//! Function level cache, so re-analyzing an edited file only walks the
+//! functions that actually changed.
+use crate::discovery::fnv1a;
+use crate::parsers::rust_parser::{ComplexityNode, ComplexityNodeKind};
+use proc_macro2::{LineColumn, TokenStream};
+use std::collections::{HashMap, HashSet};
+
+/// Hash of a function's tokens. Whitespace, plain comments and the position
+/// in the file don't affect it; doc comments do, as they are attributes.
+pub fn fingerprint(tokens: &TokenStream) -> u64 {
+    fnv1a(0, &tokens.to_string())
+}
+
+type Key = (ComplexityNodeKind, u64);
+
+/// Nodes of previously analyzed functions by kind and fingerprint.
+///
+/// Entries live as long as the last analysis of some file used them, so
+/// the versions of a function that were edited away are dropped.
+#[derive(Debug, Default)]
+pub struct FunctionCache {
+    entries: HashMap<Key, ComplexityNode>,
+    /// The keys every file used the last time it was analyzed.
+    files: HashMap<String, HashSet<Key>>,
+    /// The file being analyzed and the keys it used so far.
+    current: Option<(String, HashSet<Key>)>,
+    hits: usize,
+    misses: usize,
+}
+
+impl FunctionCache {
+    pub fn new() -> FunctionCache {
+        FunctionCache::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Lookups that found a cached node.
+    pub fn hits(&self) -> usize {
+        self.hits
+    }
+
+    /// Lookups that had to analyze the function.
+    pub fn misses(&self) -> usize {
+        self.misses
+    }
+
+    /// The cached node of a function, moved to `start` in case it was
+    /// shifted by edits above it.
+    pub(crate) fn get(
+        &mut self,
+        kind: &ComplexityNodeKind,
+        fingerprint: u64,
+        start: LineColumn,
+    ) -> Option<ComplexityNode> {
+        let key = (kind.clone(), fingerprint);
+        let mut node = match self.entries.get(&key) {
+            Some(node) => node.clone(),
+            None => {
+                self.misses += 1;
+                return None;
+            }
+        };
+        self.hits += 1;
+        self.used(key);
+
+        let from = node.line;
+        move_lines(&mut node, from, start.line);
+        node.column = start.column + 1;
+
+        Some(node)
+    }
+
+    pub(crate) fn insert(
+        &mut self,
+        kind: ComplexityNodeKind,
+        fingerprint: u64,
+        node: &ComplexityNode,
+    ) {
+        let key = (kind, fingerprint);
+        self.used(key.clone());
+        self.entries.insert(key, node.clone());
+    }
+
+    /// Start recording the entries the analysis of `file` uses.
+    pub(crate) fn begin(&mut self, file: &str) {
+        self.current = Some((file.to_string(), HashSet::new()));
+    }
+
+    /// Make the entries recorded since `begin` those of its file, dropping
+    /// the ones its previous analysis used that no file uses anymore.
+    pub(crate) fn finish(&mut self) {
+        let (file, keys) = match self.current.take() {
+            Some(current) => current,
+            None => return,
+        };
+
+        let previous = self.files.insert(file, keys).unwrap_or_default();
+        for key in previous {
+            if !self.files.values().any(|keys| keys.contains(&key)) {
+                self.entries.remove(&key);
+            }
+        }
+    }
+
+    fn used(&mut self, key: Key) {
+        if let Some((_, keys)) = self.current.as_mut() {
+            keys.insert(key);
+        }
+    }
+}
+
+/// Move `node` and everything nested in it from the line `from` to `to`.
+/// Unknown lines stay 0.
+fn move_lines(node: &mut ComplexityNode, from: usize, to: usize) {
+    if node.line > 0 {
+        node.line = node.line + to - from;
+    }
+    if let Some(deepest) = node.deepest.as_mut() {
+        deepest.line = deepest.line + to - from;
+    }
+    for child in node.children.iter_mut() {
+        move_lines(child, from, to);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::FunctionCache;
+    use crate::analyzer::AnalyzeOptions;
+    use crate::parsers::rust_parser::ComplexityTree;
+
+    fn analyze(src: &str, cache: &mut FunctionCache) -> ComplexityTree {
+        analyze_file("lib.rs", src, cache)
+    }
+
+    fn analyze_file(name: &str, src: &str, cache: &mut FunctionCache) -> ComplexityTree {
+        ComplexityTree::from_source_with_cache(
+            String::from(name),
+            src,
+            &AnalyzeOptions::default(),
+            cache,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn identical_functions_stay_separate_nodes() {
+        let mut cache = FunctionCache::new();
+        let tree = analyze(
+            "impl A { fn new() { if a {} } }\nimpl B { fn new() { if a {} } }",
+            &mut cache,
+        );
+        assert_eq!((1, 1), (cache.hits(), cache.misses()));
+
+        let methods: Vec<(usize, usize)> = tree
+            .functions()
+            .map(|(_, node)| (node.complexity, node.line))
+            .collect();
+        assert_eq!(vec![(1, 1), (1, 2)], methods);
+    }
+
+    #[test]
+    fn editing_a_function_only_invalidates_its_entry() {
+        let mut cache = FunctionCache::new();
+        analyze("fn f() { if a {} }\nfn g() { if b {} }", &mut cache);
+        assert_eq!((0, 2), (cache.hits(), cache.misses()));
+
+        let tree = analyze(
+            "fn f() { if a && c {} }\n\n\nfn g() { if b {} }",
+            &mut cache,
+        );
+        assert_eq!((1, 3), (cache.hits(), cache.misses()));
+        assert_eq!(2, cache.len());
+
+        let g = &tree.root.children[1];
+        assert_eq!((1, 4), (g.complexity, g.line));
+        assert_eq!(Some(4), g.deepest.as_ref().map(|deepest| deepest.line));
+
+        // the old `f` is gone, `g` is still there
+        analyze("fn f() { if a {} }\nfn g() { if b {} }", &mut cache);
+        assert_eq!((2, 4), (cache.hits(), cache.misses()));
+        assert_eq!(2, cache.len());
+    }
+
+    #[test]
+    fn entries_other_files_use_are_kept() {
+        let mut cache = FunctionCache::new();
+        analyze_file("a.rs", "fn f() { if a {} }", &mut cache);
+        analyze_file("b.rs", "fn f() { if a {} }", &mut cache);
+        analyze_file("a.rs", "fn f() { if a || b {} }", &mut cache);
+        assert_eq!(2, cache.len());
+
+        analyze_file("b.rs", "fn f() { if a {} }", &mut cache);
+        assert_eq!((2, 2), (cache.hits(), cache.misses()));
+    }
+
+    #[test]
+    fn nested_functions_move_along() {
+        let mut cache = FunctionCache::new();
+        let src = "fn f() {\n    fn inner() {\n        if a {}\n    }\n}";
+        analyze(src, &mut cache);
+
+        let tree = analyze(&format!("\n\n{}", src), &mut cache);
+        assert_eq!((1, 1), (cache.hits(), cache.misses()));
+        let inner = &tree.root.children[0].children[0];
+        assert_eq!(4, inner.line);
+        assert_eq!(Some(5), inner.deepest.as_ref().map(|deepest| deepest.line));
+    }
+}
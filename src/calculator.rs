@@ -1,5 +1,8 @@
-use std::collections::HashSet;
-use std::convert::From;
+use crate::parsers::error::ParseResult;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::convert::{From, TryFrom};
+use std::fmt::Write;
 
 pub type Node = u64;
 
@@ -23,9 +26,90 @@ impl Graph {
         Graph { edges }
     }
 
-    fn calculate_complexity(&self) -> i32 {
-        let edge_count: i32 = self.edges.len() as i32;
+    /// Complexity of the graph, `edges - nodes + components + exits`
+    /// clamped to at least 1.
+    ///
+    /// Components are the weakly connected ones, e.g. one per function when
+    /// the graphs of several are joined into one, so their complexities add
+    /// up. Exits are the places control can't leave: nodes without outgoing
+    /// edges, and cycles without an edge out of them, such as an endless
+    /// loop, each counting once.
+    ///
+    /// This is McCabe's `E - N + 2P` once the exits of every component are
+    /// joined in a node of their own: that adds an edge per exit and a node
+    /// per component.
+    ///
+    /// Every non-empty graph has complexity 1 or more, the empty graph is
+    /// clamped; see `is_degenerate` to detect that case.
+    pub fn calculate_complexity(&self) -> i32 {
+        let raw = self.raw_complexity();
+        // each component has at least `nodes - 1` edges and an exit, so the
+        // formula can reach 0 but never go below it.
+        debug_assert!(raw >= 0, "negative graph complexity: {}", raw);
+        let complexity = raw.max(1);
 
+        i32::try_from(complexity).unwrap_or(i32::MAX)
+    }
+
+    /// Whether the raw formula yields less than 1 for this graph, meaning
+    /// `calculate_complexity` had to clamp its result.
+    pub fn is_degenerate(&self) -> bool {
+        self.raw_complexity() < 1
+    }
+
+    /// `E - N + P + X` with the numbers of edges, nodes, components and
+    /// exits filled in, e.g. `4 - 4 + 1 + 1 = 2`, noting when
+    /// `calculate_complexity` clamps it.
+    pub fn explain(&self) -> String {
+        let mut out = format!(
+            "{} - {} + {} + {} = {}",
+            self.edges.len(),
+            self.node_count(),
+            self.component_count(),
+            self.exit_count(),
+            self.raw_complexity()
+        );
+        if self.is_degenerate() {
+            out += ", clamped to 1";
+        }
+
+        out
+    }
+
+    /// The graph in Graphviz DOT, e.g. for `dot -Tpng`: every node on a line
+    /// of its own, in ascending order, then every edge in order.
+    ///
+    /// Nodes only exist as the ends of edges, so there are no isolated nodes
+    /// to lose.
+    pub fn to_dot(&self) -> String {
+        let nodes: BTreeSet<Node> = self
+            .edges
+            .iter()
+            .flat_map(|edge| vec![edge.from, edge.to])
+            .collect();
+
+        let mut dot = String::from("digraph {\n");
+        for node in nodes {
+            writeln!(dot, "    {};", node).unwrap();
+        }
+        for edge in self.edges.iter() {
+            writeln!(dot, "    {} -> {};", edge.from, edge.to).unwrap();
+        }
+        dot.push_str("}\n");
+
+        dot
+    }
+
+    fn raw_complexity(&self) -> i64 {
+        let edge_count: i64 = self.edges.len() as i64;
+        let node_count: i64 = self.node_count() as i64;
+        let component_count: i64 = self.component_count() as i64;
+        let exit_count: i64 = self.exit_count() as i64;
+
+        edge_count - node_count + component_count + exit_count
+    }
+
+    fn node_count(&self) -> usize {
         let mut nodes: HashSet<Node> = HashSet::new();
 
         // we put all nodes in set
@@ -33,24 +117,266 @@ impl Graph {
             nodes.insert(edge.from);
             nodes.insert(edge.to);
         }
-        let node_count: i32 = nodes.len() as i32;
 
-        // we remove all nodes that are parent of some other
-        // node. In end we are left with leaf nodes only.
+        nodes.len()
+    }
+
+    /// Number of weakly connected components, found with union-find: every
+    /// edge joins the components of its ends, whichever way it points.
+    fn component_count(&self) -> usize {
+        let mut parents: HashMap<Node, Node> = HashMap::new();
+        for edge in self.edges.iter() {
+            parents.insert(edge.from, edge.from);
+            parents.insert(edge.to, edge.to);
+        }
+
+        let mut components = parents.len();
+        for edge in self.edges.iter() {
+            let from = find_root(&mut parents, edge.from);
+            let to = find_root(&mut parents, edge.to);
+            if from != to {
+                parents.insert(from, to);
+                components -= 1;
+            }
+        }
+
+        components
+    }
+
+    /// Number of strongly connected components without edges to other
+    /// components, found with Kosaraju's algorithm. A node without outgoing
+    /// edges is such a component on its own.
+    fn exit_count(&self) -> usize {
+        let mut successors: HashMap<Node, Vec<Node>> = HashMap::new();
+        let mut predecessors: HashMap<Node, Vec<Node>> = HashMap::new();
         for edge in self.edges.iter() {
-            nodes.remove(&edge.from);
+            successors.entry(edge.from).or_default().push(edge.to);
+            successors.entry(edge.to).or_default();
+            predecessors.entry(edge.to).or_default().push(edge.from);
         }
-        let exit_count: i32 = nodes.len() as i32;
 
-        edge_count - node_count + 2 * exit_count
+        // nodes in the order their depth-first search finishes
+        let mut finished = vec![];
+        let mut visited = HashSet::new();
+        let mut roots: Vec<Node> = successors.keys().copied().collect();
+        roots.sort_unstable();
+        for root in roots {
+            if !visited.insert(root) {
+                continue;
+            }
+            let mut stack = vec![(root, 0)];
+            while let Some((node, next)) = stack.pop() {
+                match successors[&node].get(next) {
+                    Some(&successor) => {
+                        stack.push((node, next + 1));
+                        if visited.insert(successor) {
+                            stack.push((successor, 0));
+                        }
+                    }
+                    None => finished.push(node),
+                }
+            }
+        }
+
+        // walking the reversed graph in reverse finishing order visits one
+        // component at a time
+        let mut component: HashMap<Node, usize> = HashMap::new();
+        let mut components = 0;
+        for &root in finished.iter().rev() {
+            if component.contains_key(&root) {
+                continue;
+            }
+            let id = components;
+            components += 1;
+            component.insert(root, id);
+            let mut stack = vec![root];
+            while let Some(node) = stack.pop() {
+                for &predecessor in predecessors.get(&node).into_iter().flatten() {
+                    if let Entry::Vacant(entry) = component.entry(predecessor) {
+                        entry.insert(id);
+                        stack.push(predecessor);
+                    }
+                }
+            }
+        }
+
+        let left: HashSet<usize> = self
+            .edges
+            .iter()
+            .filter(|edge| component[&edge.from] != component[&edge.to])
+            .map(|edge| component[&edge.from])
+            .collect();
+
+        components - left.len()
+    }
+}
+
+/// The root of the set of `node` in `parents`, pointing every node on the
+/// way straight at it.
+fn find_root(parents: &mut HashMap<Node, Node>, node: Node) -> Node {
+    let mut root = node;
+    while parents[&root] != root {
+        root = parents[&root];
+    }
+
+    let mut node = node;
+    while node != root {
+        node = parents.insert(node, root).unwrap();
     }
+
+    root
 }
 
 pub trait Parser {
-    fn parse(&mut self, file: String) -> Graph;
+    /// The graph of `file`, or why it couldn't be read or parsed.
+    fn parse(&mut self, file: String) -> ParseResult<Graph>;
 }
 
-pub fn calculate<T: Parser>(file: String, mut parser: T) -> i32 {
-    let graph: Graph = parser.parse(file);
-    graph.calculate_complexity()
+pub fn calculate<T: Parser>(file: String, mut parser: T) -> ParseResult<i32> {
+    let graph: Graph = parser.parse(file)?;
+    Ok(graph.calculate_complexity())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Edge, Graph};
+
+    #[test]
+    fn cycle_without_a_way_out_is_an_exit() {
+        // a bare cycle has no exit node, but is one exit: 2 - 2 + 2 * 1 = 2
+        let graph = Graph::new(vec![Edge::from((1, 2)), Edge::from((2, 1))]);
+
+        assert_eq!(2, graph.raw_complexity());
+        assert!(!graph.is_degenerate());
+        assert_eq!(2, graph.calculate_complexity());
+    }
+
+    #[test]
+    fn back_edges_leave_exits_alone() {
+        // `while a do b; c`: the loop can be left, so only `c` is an exit
+        let graph = Graph::new(vec![
+            Edge::from((1, 2)),
+            Edge::from((2, 3)),
+            Edge::from((3, 2)),
+            Edge::from((2, 4)),
+        ]);
+        assert_eq!(2, graph.calculate_complexity());
+
+        // `x; loop { if a { y } }`: an endless loop after a statement
+        let graph = Graph::new(vec![
+            Edge::from((1, 2)),
+            Edge::from((2, 3)),
+            Edge::from((3, 4)),
+            Edge::from((3, 2)),
+            Edge::from((4, 2)),
+        ]);
+        assert_eq!(3, graph.calculate_complexity());
+    }
+
+    #[test]
+    fn self_loops_are_exits() {
+        let graph = Graph::new(vec![Edge::from((1, 2)), Edge::from((2, 2))]);
+
+        assert_eq!(2, graph.calculate_complexity());
+    }
+
+    #[test]
+    fn empty_graph_is_clamped_to_one() {
+        let graph = Graph::new(vec![]);
+
+        assert!(graph.is_degenerate());
+        assert_eq!(1, graph.calculate_complexity());
+        assert_eq!("0 - 0 + 0 + 0 = 0, clamped to 1", graph.explain());
+    }
+
+    #[test]
+    fn forests_stay_positive() {
+        // three separate edges: a single `E - N + 2` would be 3 - 6 + 2 = -1,
+        // but every edge ends in an exit of its own
+        let graph = Graph::new(vec![
+            Edge::from((1, 2)),
+            Edge::from((3, 4)),
+            Edge::from((5, 6)),
+        ]);
+
+        assert_eq!(-1, graph.edges.len() as i64 - graph.node_count() as i64 + 2);
+        assert!(!graph.is_degenerate());
+        assert_eq!(3, graph.component_count());
+        assert_eq!(3, graph.calculate_complexity());
+        assert_eq!("3 - 6 + 3 + 3 = 3", graph.explain());
+    }
+
+    #[test]
+    fn linear_chain_has_complexity_one() {
+        let graph = Graph::new(vec![Edge::from((1, 2)), Edge::from((2, 3))]);
+
+        assert_eq!(1, graph.calculate_complexity());
+    }
+
+    #[test]
+    fn diamond_has_complexity_two() {
+        let graph = Graph::new(vec![
+            Edge::from((1, 2)),
+            Edge::from((1, 3)),
+            Edge::from((2, 4)),
+            Edge::from((3, 4)),
+        ]);
+
+        assert_eq!(2, graph.calculate_complexity());
+    }
+
+    #[test]
+    fn disconnected_components_add_up() {
+        // a chain next to a diamond
+        let graph = Graph::new(vec![
+            Edge::from((1, 2)),
+            Edge::from((10, 11)),
+            Edge::from((10, 12)),
+            Edge::from((11, 13)),
+            Edge::from((12, 13)),
+        ]);
+
+        assert_eq!(3, graph.calculate_complexity());
+    }
+
+    #[test]
+    fn dot_lists_every_node_and_edge() {
+        let graph = Graph::new(vec![
+            Edge::from((3, 1)),
+            Edge::from((1, 2)),
+            Edge::from((3, 4)),
+        ]);
+
+        assert_eq!(
+            "digraph {\n    1;\n    2;\n    3;\n    4;\n    3 -> 1;\n    1 -> 2;\n    3 -> 4;\n}\n",
+            graph.to_dot()
+        );
+        assert_eq!("digraph {\n}\n", Graph::new(vec![]).to_dot());
+    }
+
+    #[test]
+    fn well_formed_graph_is_not_clamped() {
+        // an `if` without an `else` that returns: one component, two exits
+        let graph = Graph::new(vec![Edge::from((1, 2)), Edge::from((1, 3))]);
+
+        assert!(!graph.is_degenerate());
+        assert_eq!(1, graph.component_count());
+        assert_eq!(2, graph.calculate_complexity());
+        assert_eq!("2 - 3 + 1 + 2 = 2", graph.explain());
+    }
+
+    #[test]
+    fn components_ignore_edge_direction() {
+        // 1 and 3 only meet in 2, and 4 -> 5 is apart from both
+        let graph = Graph::new(vec![
+            Edge::from((1, 2)),
+            Edge::from((3, 2)),
+            Edge::from((4, 5)),
+            Edge::from((5, 4)),
+        ]);
+
+        assert_eq!(2, graph.component_count());
+        assert_eq!(2, graph.exit_count());
+        assert_eq!("4 - 5 + 2 + 2 = 3", graph.explain());
+    }
 }
@@ -1,6 +1,20 @@
-use clap::{self, App, Arg, ArgMatches};
+use crate::analyzer::Lang;
+use crate::parsers::rust_parser::Metric;
+use crate::report::tabular::Column;
+use crate::report::text::{Severity, Sort};
+use crate::report::Format;
+use clap::{self, value_t, App, Arg, ArgMatches};
 use std::ffi::OsString;
+use std::fs;
+use std::io;
 use std::result::Result;
+use std::str::FromStr;
+
+#[cfg(feature = "serde")]
+mod file;
+
+/// Options read from the working directory, underneath the flags.
+pub const CONFIG_FILE: &str = ".complexity.toml";
 
 const APP_NAME: &str = "CYCLOMATIC COMPLEXITY";
 const VERSION: &str = "0.1";
@@ -8,7 +22,79 @@ const ABOUT: &str = "This CLI find the cyclomatic complexity associated with the
 
 #[derive(Debug)]
 pub struct Config {
-    pub file: String,
+    /// Files or glob patterns to analyze, `--file` first.
+    pub files: Vec<String>,
+    /// Directories to analyze every `.rs` file of.
+    pub dirs: Vec<String>,
+    /// Also analyze the source given on stdin, with `--stdin` or a `-` file.
+    pub stdin: bool,
+    /// Files to leave out, whether discovered or given explicitly.
+    pub exclude: Vec<glob::Pattern>,
+    pub if_else_weight: usize,
+    /// Count `&&` and `||`, unless `--no-boolean-ops`.
+    pub boolean_ops: bool,
+    /// Count every `match` arm, unless `--no-match-arms`.
+    pub match_arms: bool,
+    /// Count `?`, unless `--no-try`.
+    pub try_ops: bool,
+    pub metric: Metric,
+    pub threshold: Option<usize>,
+    pub sample: Option<f64>,
+    pub seed: u64,
+    pub format: Format,
+    pub columns: Vec<Column>,
+    pub align: bool,
+    pub sort: Option<Sort>,
+    pub line_numbers: bool,
+    pub min_complexity: usize,
+    pub summary: bool,
+    pub fail_on_regression: bool,
+    pub base: String,
+    pub baseline: Option<String>,
+    pub include_derived: bool,
+    pub include_tests: bool,
+    pub max_depth: usize,
+    pub only_public: bool,
+    pub resolve_trait_defaults: bool,
+    pub lang: Lang,
+    pub verbosity: Verbosity,
+    pub debug_graph: bool,
+    pub color: Color,
+    /// Where the colors of the text output change.
+    pub color_thresholds: Severity,
+}
+
+/// Whether to color the text output.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Color {
+    /// When stdout is a terminal and `NO_COLOR` is unset or empty.
+    Auto,
+    Always,
+    Never,
+}
+
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Color, String> {
+        match s {
+            "auto" => Ok(Color::Auto),
+            "always" => Ok(Color::Always),
+            "never" => Ok(Color::Never),
+            other => Err(format!("unknown color choice: {}", other)),
+        }
+    }
+}
+
+/// How much besides the report the binary prints, to stderr.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Verbosity {
+    /// Errors only.
+    Quiet,
+    /// Errors and warnings, e.g. about truncated functions.
+    Normal,
+    /// Also the time each file took and the size of its tree.
+    Verbose,
 }
 
 pub type ConfigResult<T> = Result<T, clap::Error>;
@@ -22,9 +108,198 @@ impl Config {
         let args: ArgMatches = parse(iter)?;
 
         Ok(Config {
-            file: args.value_of("file").unwrap().to_string(),
+            files: files(&args)
+                .into_iter()
+                .filter(|file| file != "-")
+                .collect(),
+            dirs: args
+                .values_of("dir")
+                .into_iter()
+                .flatten()
+                .map(String::from)
+                .collect(),
+            exclude: excludes(&args)?,
+            stdin: args.is_present("stdin") || files(&args).iter().any(|file| file == "-"),
+            if_else_weight: value_t!(args, "if-else-weight", usize)?,
+            boolean_ops: !args.is_present("no-boolean-ops"),
+            match_arms: !args.is_present("no-match-arms"),
+            try_ops: !args.is_present("no-try"),
+            metric: value_t!(args, "metric", Metric)?,
+            threshold: optional(&args, "threshold")?,
+            sample: optional(&args, "sample")?,
+            seed: value_t!(args, "seed", u64)?,
+            format: value_t!(args, "format", Format)?,
+            columns: columns(&args)?,
+            align: !args.is_present("no-align"),
+            sort: optional(&args, "sort")?,
+            line_numbers: args.is_present("line-numbers"),
+            min_complexity: value_t!(args, "min-complexity", usize)?,
+            summary: args.is_present("summary"),
+            fail_on_regression: args.is_present("fail-on-regression"),
+            base: args.value_of("base").unwrap().to_string(),
+            baseline: args.value_of("baseline").map(String::from),
+            include_derived: args.is_present("include-derived"),
+            include_tests: args.is_present("include-tests"),
+            max_depth: value_t!(args, "max-depth", usize)?,
+            only_public: args.is_present("only-public"),
+            resolve_trait_defaults: args.is_present("resolve-trait-defaults"),
+            lang: value_t!(args, "lang", Lang)?,
+            verbosity: if args.is_present("quiet") {
+                Verbosity::Quiet
+            } else if args.is_present("verbose") {
+                Verbosity::Verbose
+            } else {
+                Verbosity::Normal
+            },
+            debug_graph: args.is_present("debug-graph"),
+            color: value_t!(args, "color", Color)?,
+            color_thresholds: value_t!(args, "color-thresholds", Severity)?,
         })
     }
+
+    /// Like `parse`, with the options of `CONFIG_FILE` when there is one.
+    pub fn load<I, T>(iter: I) -> ConfigResult<Config>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        match fs::read_to_string(CONFIG_FILE) {
+            Ok(src) => Config::parse_with_file(iter, &src),
+            Err(err) if err.kind() == io::ErrorKind::NotFound => Config::parse(iter),
+            Err(err) => Err(file_error(err.to_string())),
+        }
+    }
+
+    /// Parse the flags in `iter` on top of the options of a config file.
+    ///
+    /// Every key of the file is the long name of a flag, with dashes or
+    /// underscores, e.g. `threshold = 15`, `format = "csv"` or
+    /// `include-tests = true`. An array repeats its flag, e.g.
+    /// `exclude = ["**/tests/**", "benches/*"]`. A flag given in `iter`
+    /// replaces the key of the same name, arrays included, and flags given
+    /// nowhere keep their defaults. Reading the file needs the `serde`
+    /// feature.
+    pub fn parse_with_file<I, T>(iter: I, src: &str) -> ConfigResult<Config>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let mut args: Vec<OsString> = iter.into_iter().map(Into::into).collect();
+        let from_file = file_args(src, &given_flags(&args)).map_err(file_error)?;
+
+        // after the program name, so they can't be taken for its value
+        let at = args.len().min(1);
+        args.splice(at..at, from_file.into_iter().map(OsString::from));
+        Config::parse(args)
+    }
+}
+
+/// Flags with a short form, and their long one.
+const SHORT_FLAGS: [(&str, &str); 2] = [("-q", "quiet"), ("-v", "verbose")];
+
+/// The flags whose value the command line sets, e.g. `threshold` for
+/// `--threshold 5` or `--threshold=5`.
+fn given_flags(args: &[OsString]) -> Vec<String> {
+    args.iter()
+        .skip(1)
+        .filter_map(|arg| arg.to_str())
+        .take_while(|arg| *arg != "--")
+        .filter_map(|arg| match arg.strip_prefix("--") {
+            Some(flag) => Some(flag.split('=').next().unwrap_or(flag)),
+            None => SHORT_FLAGS
+                .iter()
+                .find(|(short, _)| *short == arg)
+                .map(|(_, long)| *long),
+        })
+        .map(String::from)
+        .collect()
+}
+
+/// The flags standing for the keys of the config file `src` that `given`
+/// leaves unset.
+#[cfg(feature = "serde")]
+fn file_args(src: &str, given: &[String]) -> Result<Vec<String>, String> {
+    Ok(file::parse(src)?.args(given))
+}
+
+#[cfg(not(feature = "serde"))]
+fn file_args(_: &str, _: &[String]) -> Result<Vec<String>, String> {
+    Err(String::from("reading it needs the serde feature"))
+}
+
+fn file_error(msg: String) -> clap::Error {
+    clap::Error::with_description(
+        &format!("{}: {}", CONFIG_FILE, msg),
+        clap::ErrorKind::InvalidValue,
+    )
+}
+
+/// Options of the `trend` utility.
+#[derive(Debug)]
+pub struct TrendConfig {
+    /// Pairs of (label, path) for every report, in the order given. Reports
+    /// without a matching `--label` are labelled by their path.
+    pub reports: Vec<(String, String)>,
+}
+
+impl TrendConfig {
+    pub fn parse<I, T>(iter: I) -> ConfigResult<TrendConfig>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+    {
+        let args: ArgMatches = parse_trend(iter)?;
+
+        let mut labels = args.values_of("label").into_iter().flatten();
+        let reports = args
+            .values_of("reports")
+            .unwrap()
+            .map(|report| {
+                let label = labels.next().unwrap_or(report);
+                (label.to_string(), report.to_string())
+            })
+            .collect();
+
+        Ok(TrendConfig { reports })
+    }
+}
+
+fn optional<T>(args: &ArgMatches, name: &str) -> ConfigResult<Option<T>>
+where
+    T: FromStr,
+{
+    if args.is_present(name) {
+        Ok(Some(value_t!(args, name, T)?))
+    } else {
+        Ok(None)
+    }
+}
+
+fn files(args: &ArgMatches) -> Vec<String> {
+    args.values_of("file")
+        .into_iter()
+        .flatten()
+        .chain(args.values_of("files").into_iter().flatten())
+        .map(String::from)
+        .collect()
+}
+
+fn excludes(args: &ArgMatches) -> ConfigResult<Vec<glob::Pattern>> {
+    args.values_of("exclude")
+        .into_iter()
+        .flatten()
+        .map(glob::Pattern::new)
+        .collect::<Result<Vec<glob::Pattern>, glob::PatternError>>()
+        .map_err(|err| clap::Error::value_validation_auto(err.to_string()))
+}
+
+fn columns(args: &ArgMatches) -> ConfigResult<Vec<Column>> {
+    args.value_of("columns")
+        .unwrap()
+        .split(',')
+        .map(|column| column.trim().parse::<Column>())
+        .collect::<Result<Vec<Column>, String>>()
+        .map_err(clap::Error::value_validation_auto)
 }
 
 fn parse<I, T>(iter: I) -> clap::Result<ArgMatches<'static>>
@@ -37,30 +312,692 @@ where
         .about(ABOUT)
         .arg(
             Arg::with_name("file")
-                .help("file name to check cyclomatic complixity for")
+                .help("file name or glob pattern to check cyclomatic complixity for")
                 .long("file")
-                .required(true)
+                .required_unless_one(&["files", "dir", "stdin"])
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("exclude")
+                .help("skip files whose path matches this glob, e.g. '**/tests/**', even when named explicitly (repeatable)")
+                .long("exclude")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("stdin")
+                .help("read source from stdin, reported as <stdin>; same as a - file")
+                .long("stdin"),
+        )
+        .arg(
+            Arg::with_name("dir")
+                .help("directory to analyze every .rs file of, skipping target/ (repeatable)")
+                .long("dir")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .arg(
+            Arg::with_name("files")
+                .help("more file names or glob patterns, each reported in its own section")
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("if-else-weight")
+                .help("extra complexity added by a trailing else block")
+                .long("if-else-weight")
+                .takes_value(true)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("metric")
+                .help("complexity to report and check: cyclomatic, or cognitive, where nesting costs extra")
+                .long("metric")
+                .takes_value(true)
+                .possible_values(&["cyclomatic", "cognitive"])
+                .default_value("cyclomatic"),
+        )
+        .arg(
+            Arg::with_name("no-boolean-ops")
+                .help("don't count && and || as decisions")
+                .long("no-boolean-ops"),
+        )
+        .arg(
+            Arg::with_name("no-match-arms")
+                .help("count a match as one decision, however many arms it has")
+                .long("no-match-arms"),
+        )
+        .arg(
+            Arg::with_name("no-try")
+                .help("don't count ? as a decision")
+                .long("no-try"),
+        )
+        .arg(
+            Arg::with_name("threshold")
+                .help("exit with an error if any function is more complex than this (also the budget of --format review, sarif and plain-paths, 10 by default)")
+                .long("threshold")
                 .takes_value(true),
         )
+        .arg(
+            Arg::with_name("sample")
+                .help("analyze only this fraction (0.0-1.0) of the discovered files")
+                .long("sample")
+                .takes_value(true)
+                .validator(validate_fraction),
+        )
+        .arg(
+            Arg::with_name("seed")
+                .help("seed used to pick the files when sampling")
+                .long("seed")
+                .takes_value(true)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("format")
+                .help("output format")
+                .long("format")
+                .takes_value(true)
+                .possible_values(&["text", "csv", "tsv", "ndjson", "json", "review", "sarif", "plain-paths", "dot", "markdown"])
+                .default_value("text"),
+        )
+        .arg(
+            Arg::with_name("columns")
+                .help("comma separated columns to emit for csv and tsv output: file, path, kind, name, complexity, line, loc (lines spanned) or density (complexity per line)")
+                .long("columns")
+                .takes_value(true)
+                .default_value("file,path,kind,name,complexity"),
+        )
+        .arg(
+            Arg::with_name("no-align")
+                .help("don't align the complexity column of the text output")
+                .long("no-align"),
+        )
+        .arg(
+            Arg::with_name("sort")
+                .help("list only the functions of the text output, most complex first, by name or most complexity per line first")
+                .long("sort")
+                .takes_value(true)
+                .possible_values(&["complexity", "name", "density"]),
+        )
+        .arg(
+            Arg::with_name("line-numbers")
+                .help("print the line every function of the text output starts on after its name")
+                .long("line-numbers"),
+        )
+        .arg(
+            Arg::with_name("summary")
+                .help("end the text output with statistics over all functions and those of every directory, or print them as the json output instead of the trees")
+                .long("summary"),
+        )
+        .arg(
+            Arg::with_name("min-complexity")
+                .help("leave functions less complex than this out of the text and markdown output")
+                .long("min-complexity")
+                .takes_value(true)
+                .default_value("0"),
+        )
+        .arg(
+            Arg::with_name("fail-on-regression")
+                .help("exit with an error if any function got more complex since the merge-base with --base")
+                .long("fail-on-regression"),
+        )
+        .arg(
+            Arg::with_name("base")
+                .help("git ref to compare against for --fail-on-regression")
+                .long("base")
+                .takes_value(true)
+                .default_value("main"),
+        )
+        .arg(
+            Arg::with_name("baseline")
+                .help("exit with an error if any function got more complex than in this --format json report, or is new and over --threshold (10 by default)")
+                .long("baseline")
+                .takes_value(true),
+        )
+        .arg(
+            Arg::with_name("include-derived")
+                .help("analyze #[automatically_derived] impls, e.g. in cargo expand output")
+                .long("include-derived"),
+        )
+        .arg(
+            Arg::with_name("include-tests")
+                .help("analyze #[cfg(test)] items and #[test] functions, which are skipped by default")
+                .long("include-tests"),
+        )
+        .arg(
+            Arg::with_name("max-depth")
+                .help("stop descending into expressions nested deeper than this")
+                .long("max-depth")
+                .takes_value(true)
+                .default_value("512"),
+        )
+        .arg(
+            Arg::with_name("only-public")
+                .help("only report and check pub functions and trait impl methods, the others still count in totals")
+                .long("only-public"),
+        )
+        .arg(
+            Arg::with_name("resolve-trait-defaults")
+                .help("count the trait default methods an impl doesn't override as methods of the impl (traits declared in the same file only, not through use)")
+                .long("resolve-trait-defaults"),
+        )
+        .arg(
+            Arg::with_name("lang")
+                .help("language to analyze files as; auto picks it by extension (.rs, .pas, .dpr, .inc), and stdin is rust")
+                .long("lang")
+                .takes_value(true)
+                .possible_values(&["rust", "delphi", "auto"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::with_name("quiet")
+                .help("print nothing but the report and errors")
+                .short("q")
+                .long("quiet")
+                .overrides_with("verbose"),
+        )
+        .arg(
+            Arg::with_name("verbose")
+                .help("also print how long every file took and how many nodes it has, to stderr")
+                .short("v")
+                .long("verbose")
+                .overrides_with("quiet"),
+        )
+        .arg(
+            Arg::with_name("debug-graph")
+                .help("print E - N + P + X (edges, nodes, components, exits) of the control-flow graph of every Delphi routine to stderr, noting where it was clamped to 1")
+                .long("debug-graph"),
+        )
+        .arg(
+            Arg::with_name("color")
+                .help("color the complexities of functions in the text output; auto does on a terminal unless NO_COLOR is set")
+                .long("color")
+                .takes_value(true)
+                .possible_values(&["auto", "always", "never"])
+                .default_value("auto"),
+        )
+        .arg(
+            Arg::with_name("color-thresholds")
+                .help("complexities up to the first are green, up to the second yellow and red beyond")
+                .long("color-thresholds")
+                .takes_value(true)
+                .default_value("5,10"),
+        )
         .get_matches_from_safe(iter)
 }
 
+fn parse_trend<I, T>(iter: I) -> clap::Result<ArgMatches<'static>>
+where
+    I: IntoIterator<Item = T>,
+    T: Into<OsString> + Clone,
+{
+    App::new("trend")
+        .version(VERSION)
+        .about("Aggregate NDJSON complexity reports into one CSV row per run")
+        .arg(
+            Arg::with_name("reports")
+                .help("NDJSON reports, one per run")
+                .required(true)
+                .multiple(true),
+        )
+        .arg(
+            Arg::with_name("label")
+                .help("label of each report (e.g. a commit SHA), in the same order")
+                .long("label")
+                .takes_value(true)
+                .multiple(true)
+                .number_of_values(1),
+        )
+        .get_matches_from_safe(iter)
+}
+
+fn validate_fraction(value: String) -> Result<(), String> {
+    match value.parse::<f64>() {
+        Ok(fraction) if (0.0..=1.0).contains(&fraction) => Ok(()),
+        _ => Err(String::from("must be a number between 0.0 and 1.0")),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::Config;
+    use super::{Color, Config, TrendConfig, Verbosity};
+    use crate::analyzer::Lang;
+    use crate::parsers::rust_parser::Metric;
+    use crate::report::tabular::{Column, DEFAULT_COLUMNS};
+    use crate::report::text::{Severity, Sort};
+    use crate::report::Format;
     use rstest::rstest;
 
     #[test]
     fn valid_args() {
         let args = vec!["prog", "--file", "test_file"];
         let config: Config = Config::parse(args).ok().unwrap();
-        assert_eq!("test_file", config.file);
+        assert_eq!(vec!["test_file"], config.files);
+        assert!(config.dirs.is_empty());
+        assert!(!config.stdin);
+        assert!(config.exclude.is_empty());
+        assert_eq!(0, config.if_else_weight);
+        assert!(config.boolean_ops);
+        assert!(config.match_arms);
+        assert!(config.try_ops);
+        assert_eq!(Metric::Cyclomatic, config.metric);
+        assert_eq!(None, config.threshold);
+        assert_eq!(None, config.sample);
+        assert_eq!(0, config.seed);
+        assert_eq!(Format::Text, config.format);
+        assert_eq!(DEFAULT_COLUMNS.to_vec(), config.columns);
+        assert!(config.align);
+        assert_eq!(None, config.sort);
+        assert!(!config.line_numbers);
+        assert_eq!(0, config.min_complexity);
+        assert!(!config.summary);
+        assert!(!config.fail_on_regression);
+        assert_eq!("main", config.base);
+        assert_eq!(None, config.baseline);
+        assert!(!config.include_derived);
+        assert!(!config.include_tests);
+        assert_eq!(Lang::Auto, config.lang);
+        assert_eq!(512, config.max_depth);
+        assert!(!config.only_public);
+        assert!(!config.resolve_trait_defaults);
+    }
+
+    #[test]
+    fn positional_files_follow_the_file_arg() {
+        let args = vec!["prog", "a.rs", "--file", "test_file", "b.rs"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(vec!["test_file", "a.rs", "b.rs"], config.files);
+
+        let args = vec!["prog", "a.rs", "src/*.rs"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(vec!["a.rs", "src/*.rs"], config.files);
+    }
+
+    #[rstest]
+    #[case(vec!["prog", "--stdin"])]
+    #[case(vec!["prog", "-"])]
+    fn stdin_args(#[case] args: Vec<&str>) {
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(config.files.is_empty());
+        assert!(config.stdin);
+    }
+
+    #[test]
+    fn exclude_args() {
+        let args = vec![
+            "prog",
+            "--dir",
+            "src",
+            "--exclude",
+            "**/tests/**",
+            "--exclude",
+            "**/*_generated.rs",
+        ];
+        let config: Config = Config::parse(args).ok().unwrap();
+        let excludes: Vec<&str> = config.exclude.iter().map(|p| p.as_str()).collect();
+        assert_eq!(vec!["**/tests/**", "**/*_generated.rs"], excludes);
+    }
+
+    #[test]
+    fn dir_args() {
+        let args = vec!["prog", "--dir", "src", "--dir", "benches"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(config.files.is_empty());
+        assert_eq!(vec!["src", "benches"], config.dirs);
+    }
+
+    #[test]
+    fn resolve_trait_defaults_arg() {
+        let args = vec!["prog", "--file", "test_file", "--resolve-trait-defaults"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(config.resolve_trait_defaults);
+    }
+
+    #[test]
+    fn only_public_arg() {
+        let args = vec!["prog", "--file", "test_file", "--only-public"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(config.only_public);
+    }
+
+    #[test]
+    fn max_depth_arg() {
+        let args = vec!["prog", "--file", "test_file", "--max-depth", "64"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(64, config.max_depth);
+    }
+
+    #[test]
+    fn include_derived_arg() {
+        let args = vec!["prog", "--file", "test_file", "--include-derived"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(config.include_derived);
+    }
+
+    #[test]
+    fn include_tests_arg() {
+        let args = vec!["prog", "--file", "test_file", "--include-tests"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(config.include_tests);
+    }
+
+    #[test]
+    fn fail_on_regression_args() {
+        let args = vec![
+            "prog",
+            "--file",
+            "test_file",
+            "--fail-on-regression",
+            "--base",
+            "dev",
+        ];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(config.fail_on_regression);
+        assert_eq!("dev", config.base);
+    }
+
+    #[test]
+    fn json_format_arg() {
+        let args = vec!["prog", "--file", "test_file", "--format", "json"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Format::Json, config.format);
+    }
+
+    #[test]
+    fn no_construct_args() {
+        let args = vec![
+            "prog",
+            "--file",
+            "test_file",
+            "--no-boolean-ops",
+            "--no-match-arms",
+            "--no-try",
+        ];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(!config.boolean_ops);
+        assert!(!config.match_arms);
+        assert!(!config.try_ops);
+    }
+
+    #[test]
+    fn metric_arg() {
+        let args = vec!["prog", "--file", "test_file", "--metric", "cognitive"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Metric::Cognitive, config.metric);
+    }
+
+    #[test]
+    fn lang_arg() {
+        let args = vec!["prog", "--file", "test_file", "--lang", "delphi"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Lang::Delphi, config.lang);
+    }
+
+    #[rstest]
+    #[case(vec!["prog", "--file", "test_file"], Verbosity::Normal)]
+    #[case(vec!["prog", "--file", "test_file", "-q"], Verbosity::Quiet)]
+    #[case(vec!["prog", "--file", "test_file", "--verbose"], Verbosity::Verbose)]
+    #[case(vec!["prog", "--file", "test_file", "-v", "--quiet"], Verbosity::Quiet)]
+    #[case(vec!["prog", "--file", "test_file", "-q", "-v"], Verbosity::Verbose)]
+    fn verbosity_args(#[case] args: Vec<&str>, #[case] expected: Verbosity) {
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(expected, config.verbosity);
+    }
+
+    #[test]
+    fn debug_graph_arg() {
+        let config: Config = Config::parse(vec!["prog", "--file", "test_file"]).unwrap();
+        assert!(!config.debug_graph);
+
+        let args = vec!["prog", "--file", "test_file", "--debug-graph"];
+        let config: Config = Config::parse(args).unwrap();
+        assert!(config.debug_graph);
+    }
+
+    #[test]
+    fn color_args() {
+        let config: Config = Config::parse(vec!["prog", "--file", "test_file"]).unwrap();
+        assert_eq!(Color::Auto, config.color);
+        assert_eq!(Severity::default(), config.color_thresholds);
+
+        let args = vec![
+            "prog",
+            "--file",
+            "test_file",
+            "--color",
+            "always",
+            "--color-thresholds",
+            "3,7",
+        ];
+        let config: Config = Config::parse(args).unwrap();
+        assert_eq!(Color::Always, config.color);
+        assert_eq!(Severity { low: 3, high: 7 }, config.color_thresholds);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn short_flags_replace_the_keys_of_the_config_file() {
+        let config =
+            Config::parse_with_file(vec!["prog", "--file", "test_file", "-q"], "verbose = true")
+                .unwrap();
+        assert_eq!(Verbosity::Quiet, config.verbosity);
+
+        let config =
+            Config::parse_with_file(vec!["prog", "--file", "test_file", "-q"], "quiet = true")
+                .unwrap();
+        assert_eq!(Verbosity::Quiet, config.verbosity);
+    }
+
+    #[test]
+    fn dot_format_arg() {
+        let args = vec!["prog", "--file", "test_file", "--format", "dot"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Format::Dot, config.format);
+    }
+
+    #[test]
+    fn markdown_format_arg() {
+        let args = vec!["prog", "--file", "test_file", "--format", "markdown"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Format::Markdown, config.format);
+    }
+
+    #[test]
+    fn sort_arg() {
+        let args = vec!["prog", "--file", "test_file", "--sort", "name"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Some(Sort::Name), config.sort);
+    }
+
+    #[test]
+    fn sort_density_arg() {
+        let args = vec!["prog", "--file", "test_file", "--sort", "density"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Some(Sort::Density), config.sort);
+    }
+
+    #[test]
+    fn line_numbers_arg() {
+        let args = vec!["prog", "--file", "test_file", "--line-numbers"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(config.line_numbers);
+    }
+
+    #[test]
+    fn summary_arg() {
+        let args = vec!["prog", "--file", "test_file", "--summary"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(config.summary);
+    }
+
+    #[test]
+    fn baseline_arg() {
+        let args = vec!["prog", "--file", "test_file", "--baseline", "main.json"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Some(String::from("main.json")), config.baseline);
+    }
+
+    #[test]
+    fn min_complexity_arg() {
+        let args = vec!["prog", "--file", "test_file", "--min-complexity", "3"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(3, config.min_complexity);
+    }
+
+    #[test]
+    fn no_align_arg() {
+        let args = vec!["prog", "--file", "test_file", "--no-align"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert!(!config.align);
+    }
+
+    #[test]
+    fn format_and_columns_args() {
+        let args = vec![
+            "prog",
+            "--file",
+            "test_file",
+            "--format",
+            "tsv",
+            "--columns",
+            "name,complexity",
+        ];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Format::Tsv, config.format);
+        assert_eq!(vec![Column::Name, Column::Complexity], config.columns);
+    }
+
+    #[test]
+    fn sample_args() {
+        let args = vec!["prog", "--file", "*.rs", "--sample", "0.25", "--seed", "7"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Some(0.25), config.sample);
+        assert_eq!(7, config.seed);
+    }
+
+    #[test]
+    fn threshold_arg() {
+        let args = vec!["prog", "--file", "test_file", "--threshold", "10"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(Some(10), config.threshold);
+    }
+
+    #[test]
+    fn if_else_weight_arg() {
+        let args = vec!["prog", "--file", "test_file", "--if-else-weight", "1"];
+        let config: Config = Config::parse(args).ok().unwrap();
+        assert_eq!(1, config.if_else_weight);
+    }
+
+    #[test]
+    fn trend_args_pair_labels_with_reports() {
+        let args = vec!["trend", "--label", "abc", "a.ndjson", "b.ndjson"];
+        let config: TrendConfig = TrendConfig::parse(args).ok().unwrap();
+        assert_eq!(
+            vec![
+                (String::from("abc"), String::from("a.ndjson")),
+                (String::from("b.ndjson"), String::from("b.ndjson")),
+            ],
+            config.reports
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_file_sets_options() {
+        let src = "
+            threshold = 15
+            format = \"csv\"
+            metric = \"cognitive\"
+            exclude = [\"**/tests/**\", \"benches/*\"]
+            include-tests = true
+            min_complexity = 3
+            only-public = false
+        ";
+        let config = Config::parse_with_file(vec!["prog", "--file", "test_file"], src).unwrap();
+
+        assert_eq!(Some(15), config.threshold);
+        assert_eq!(Format::Csv, config.format);
+        assert_eq!(Metric::Cognitive, config.metric);
+        assert_eq!(2, config.exclude.len());
+        assert!(config.include_tests);
+        assert_eq!(3, config.min_complexity);
+        assert!(!config.only_public);
+        assert_eq!(vec!["test_file"], config.files);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn flags_replace_the_keys_of_the_config_file() {
+        let src = "threshold = 15\nformat = \"csv\"\nexclude = [\"a/*\", \"b/*\"]";
+        let args = vec!["prog", "--threshold=3", "--exclude", "c/*", "src/lib.rs"];
+        let config = Config::parse_with_file(args, src).unwrap();
+
+        assert_eq!(Some(3), config.threshold);
+        assert_eq!(Format::Csv, config.format);
+        assert_eq!(vec![glob::Pattern::new("c/*").unwrap()], config.exclude);
+        assert_eq!(vec!["src/lib.rs"], config.files);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_file_can_name_what_to_analyze() {
+        let config = Config::parse_with_file(vec!["prog"], "dir = [\"src\"]").unwrap();
+
+        assert_eq!(vec!["src"], config.dirs);
+    }
+
+    #[cfg(feature = "serde")]
+    #[rstest]
+    #[case("threshold = 1\nthreshold = 2")]
+    #[case("threshold = \"many\"")]
+    #[case("format = \"yaml\"")]
+    #[case("colour = \"blue\"")]
+    #[case("exclude = [[\"a\"]]")]
+    fn invalid_config_files_are_errors(#[case] src: &str) {
+        assert!(Config::parse_with_file(vec!["prog", "--file", "test_file"], src).is_err());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn config_file_errors_name_the_file_and_line() {
+        let err = Config::parse_with_file(vec!["prog", "--file", "test_file"], "\nthreshold =")
+            .err()
+            .unwrap();
+
+        assert!(err.message.contains(".complexity.toml: line 2: "));
+    }
+
+    #[cfg(not(feature = "serde"))]
+    #[test]
+    fn config_files_need_serde() {
+        assert!(Config::parse_with_file(vec!["prog", "--file", "test_file"], "").is_err());
+    }
+
+    #[test]
+    fn trend_requires_reports() {
+        assert!(TrendConfig::parse(vec!["trend"]).is_err());
     }
 
     #[rstest]
     #[case(vec!["prog", "--file"])]
     #[case(vec!["prog"])]
     #[case(vec!["prog", "--alien", "ben10"])]
+    #[case(vec!["prog", "--file", "test_file", "--if-else-weight", "many"])]
+    #[case(vec!["prog", "--file", "test_file", "--threshold", "-1"])]
+    #[case(vec!["prog", "--file", "test_file", "--sample", "1.5"])]
+    #[case(vec!["prog", "--file", "test_file", "--seed", "abc"])]
+    #[case(vec!["prog", "--file", "test_file", "--max-depth", "deep"])]
+    #[case(vec!["prog", "--file", "test_file", "--format", "yaml"])]
+    #[case(vec!["prog", "--file", "test_file", "--sort", "size"])]
+    #[case(vec!["prog", "--file", "test_file", "--lang", "pascal"])]
+    #[case(vec!["prog", "--file", "test_file", "--metric", "halstead"])]
+    #[case(vec!["prog", "--file", "test_file", "--exclude", "***"])]
+    #[case(vec!["prog", "--file", "test_file", "--min-complexity", "-1"])]
+    #[case(vec!["prog", "--file", "test_file", "--columns", "name,colour"])]
+    #[case(vec!["prog", "--file", "test_file", "--color", "sometimes"])]
+    #[case(vec!["prog", "--file", "test_file", "--color-thresholds", "10,5"])]
     fn invalid_args_test(#[case] input: Vec<&str>) {
         assert!(Config::parse(input).is_err());
     }
@@ -0,0 +1,221 @@
+//! The options of `.complexity.toml`: one key per flag, named after its long
+//! form with dashes or underscores.
+use serde::Deserialize;
+
+/// Declare `Options`, with a field of type `$ty` for the flag of every
+/// `$field`, e.g. `--min-complexity` for `min_complexity`.
+macro_rules! options {
+    ($($field:ident: $ty:ty,)*) => {
+        #[derive(Debug, Default, Deserialize)]
+        #[serde(deny_unknown_fields, rename_all = "kebab-case")]
+        pub(super) struct Options {
+            $($field: Option<$ty>,)*
+        }
+
+        impl Options {
+            /// The flags standing for the options that `given` leaves unset.
+            pub(super) fn args(self, given: &[String]) -> Vec<String> {
+                let mut args = vec![];
+                $(
+                    let flag = stringify!($field).replace('_', "-");
+                    if let Some(value) = self.$field {
+                        if !given.contains(&flag) {
+                            value.push_args(&flag, &mut args);
+                        }
+                    }
+                )*
+                args
+            }
+        }
+    };
+}
+
+options! {
+    file: String,
+    exclude: Vec<String>,
+    stdin: bool,
+    dir: Vec<String>,
+    if_else_weight: usize,
+    metric: String,
+    no_boolean_ops: bool,
+    no_match_arms: bool,
+    no_try: bool,
+    threshold: usize,
+    sample: f64,
+    seed: u64,
+    format: String,
+    columns: String,
+    no_align: bool,
+    sort: String,
+    line_numbers: bool,
+    summary: bool,
+    min_complexity: usize,
+    fail_on_regression: bool,
+    base: String,
+    baseline: String,
+    include_derived: bool,
+    include_tests: bool,
+    max_depth: usize,
+    only_public: bool,
+    resolve_trait_defaults: bool,
+    lang: String,
+    quiet: bool,
+    verbose: bool,
+    debug_graph: bool,
+    color: String,
+    color_thresholds: String,
+}
+
+/// The options of `src`. Syntax errors name the line they were found on.
+pub(super) fn parse(src: &str) -> Result<Options, String> {
+    let table: toml::Table = src
+        .parse()
+        .map_err(|err: toml::de::Error| match err.span() {
+            Some(span) => {
+                let line = src[..span.start].matches('\n').count() + 1;
+                format!("line {}: {}", line, one_line(err.message()))
+            }
+            None => one_line(err.message()),
+        })?;
+
+    let mut options = toml::Table::new();
+    for (key, value) in table {
+        let flag = key.replace('_', "-");
+        if options.insert(flag.clone(), value).is_some() {
+            return Err(format!("{} is set twice", flag));
+        }
+    }
+
+    toml::Value::Table(options)
+        .try_into()
+        .map_err(|err: toml::de::Error| one_line(&err.to_string()))
+}
+
+/// `msg` without its line breaks, e.g. before the key of a type error.
+fn one_line(msg: &str) -> String {
+    msg.trim().replace('\n', ", ")
+}
+
+/// How an option is given on the command line.
+trait FlagValue {
+    fn push_args(self, flag: &str, args: &mut Vec<String>);
+}
+
+/// A switch, given when true.
+impl FlagValue for bool {
+    fn push_args(self, flag: &str, args: &mut Vec<String>) {
+        if self {
+            args.push(format!("--{}", flag));
+        }
+    }
+}
+
+/// A repeatable flag, given once per value.
+impl FlagValue for Vec<String> {
+    fn push_args(self, flag: &str, args: &mut Vec<String>) {
+        for value in self {
+            value.push_args(flag, args);
+        }
+    }
+}
+
+impl FlagValue for String {
+    fn push_args(self, flag: &str, args: &mut Vec<String>) {
+        args.push(format!("--{}", flag));
+        args.push(self);
+    }
+}
+
+impl FlagValue for usize {
+    fn push_args(self, flag: &str, args: &mut Vec<String>) {
+        self.to_string().push_args(flag, args);
+    }
+}
+
+impl FlagValue for u64 {
+    fn push_args(self, flag: &str, args: &mut Vec<String>) {
+        self.to_string().push_args(flag, args);
+    }
+}
+
+impl FlagValue for f64 {
+    fn push_args(self, flag: &str, args: &mut Vec<String>) {
+        self.to_string().push_args(flag, args);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::parse;
+    use rstest::rstest;
+
+    fn args(src: &str, given: &[&str]) -> Vec<String> {
+        let given: Vec<String> = given.iter().map(|flag| flag.to_string()).collect();
+        parse(src).unwrap().args(&given)
+    }
+
+    #[test]
+    fn options_become_flags() {
+        let src = "# options for CI
+            threshold = 15
+            format = \"csv\" # for the dashboard
+            sample = 0.25
+            include-tests = true
+            only_public = false
+            exclude = [
+                \"**/tests/**\", # fixtures are generated
+                'benches\\*',
+            ]
+        ";
+
+        assert_eq!(
+            vec![
+                "--exclude",
+                "**/tests/**",
+                "--exclude",
+                "benches\\*",
+                "--threshold",
+                "15",
+                "--sample",
+                "0.25",
+                "--format",
+                "csv",
+                "--include-tests",
+            ],
+            args(src, &[])
+        );
+    }
+
+    #[test]
+    fn given_flags_are_left_out() {
+        let src = "threshold = 15\nexclude = [\"a/*\"]\nsummary = true";
+
+        assert_eq!(
+            vec!["--threshold", "15"],
+            args(src, &["exclude", "summary"])
+        );
+    }
+
+    #[test]
+    fn empty_files_have_no_options() {
+        assert!(args("", &[]).is_empty());
+        assert!(args("\n# nothing yet\n\n", &[]).is_empty());
+    }
+
+    #[rstest]
+    #[case("threshold = 1\nthreshold = 2", "line 2: duplicate key")]
+    #[case(
+        "min-complexity = 1\nmin_complexity = 2",
+        "min-complexity is set twice"
+    )]
+    #[case("\n\nformat = csv", "line 3: ")]
+    #[case("threshold = \"many\"", "invalid type: string \"many\"")]
+    #[case("threshold = -1", "invalid value: integer `-1`")]
+    #[case("colour = \"blue\"", "unknown field `colour`")]
+    #[case("[options]\nthreshold = 1", "unknown field `options`")]
+    #[case("exclude = [[\"a\"]]", "invalid type: sequence")]
+    fn invalid_files_are_errors(#[case] src: &str, #[case] expected: &str) {
+        let err = parse(src).err().unwrap();
+        assert!(err.starts_with(expected), "{}", err);
+    }
+}
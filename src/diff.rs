@@ -0,0 +1,150 @@
+//! Module for comparing two analyses of the same code.
+use crate::parsers::rust_parser::ComplexityTree;
+use crate::report::rows;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+/// A function whose complexity grew between two analyses.
+#[derive(Debug, PartialEq)]
+pub struct Regression {
+    pub file: String,
+    pub path: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+impl fmt::Display for Regression {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: [{}] Complexity {} => {}",
+            self.file, self.path, self.before, self.after
+        )
+    }
+}
+
+/// A function missing from the earlier analysis that is already over budget.
+#[derive(Debug, PartialEq)]
+pub struct Addition {
+    pub file: String,
+    pub path: String,
+    pub complexity: usize,
+    pub budget: usize,
+}
+
+impl fmt::Display for Addition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: [{}] Complexity {} (new, budget {})",
+            self.file, self.path, self.complexity, self.budget
+        )
+    }
+}
+
+/// Functions of `after` that are more complex than the same function (matched
+/// by file and path) in `before`. Functions missing from `before` are new
+/// rather than regressed, so they aren't reported.
+pub fn regressions(before: &[ComplexityTree], after: &[ComplexityTree]) -> Vec<Regression> {
+    let mut previous: HashMap<(&str, String), usize> = HashMap::new();
+    for tree in before {
+        for row in rows(tree) {
+            previous.insert((row.file, row.path), row.node.complexity);
+        }
+    }
+
+    let mut regressions = vec![];
+    for tree in after {
+        for row in rows(tree) {
+            let complexity = row.node.complexity;
+            if let Some(&old) = previous.get(&(row.file, row.path.clone())) {
+                if complexity > old {
+                    regressions.push(Regression {
+                        file: row.file.to_string(),
+                        path: row.path,
+                        before: old,
+                        after: complexity,
+                    });
+                }
+            }
+        }
+    }
+
+    regressions
+}
+
+/// Functions of `after` missing from `before` (matched by file and path)
+/// that are more complex than `budget`.
+pub fn additions(
+    before: &[ComplexityTree],
+    after: &[ComplexityTree],
+    budget: usize,
+) -> Vec<Addition> {
+    let mut previous: HashSet<(&str, String)> = HashSet::new();
+    for tree in before {
+        for row in rows(tree) {
+            previous.insert((row.file, row.path));
+        }
+    }
+
+    let mut additions = vec![];
+    for tree in after {
+        for row in rows(tree) {
+            let complexity = row.node.complexity;
+            if complexity > budget && !previous.contains(&(row.file, row.path.clone())) {
+                additions.push(Addition {
+                    file: row.file.to_string(),
+                    path: row.path,
+                    complexity,
+                    budget,
+                });
+            }
+        }
+    }
+
+    additions
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{additions, regressions, Addition, Regression};
+    use crate::analyzer::Analyzer;
+    use crate::parsers::rust_parser::ComplexityTree;
+
+    fn tree(src: &str) -> ComplexityTree {
+        Analyzer::default().analyze_source("lib.rs", src).unwrap()
+    }
+
+    #[test]
+    fn reports_only_increases() {
+        let before = tree("fn a() { if x {} } fn b() { if x {} if y {} } fn c() {}");
+        let after =
+            tree("fn a() { if x {} if y {} } fn b() { if x {} } fn c() {} fn d() { if x {} }");
+
+        assert_eq!(
+            vec![Regression {
+                file: String::from("lib.rs"),
+                path: String::from("Fn: a"),
+                before: 1,
+                after: 2,
+            }],
+            regressions(&[before], &[after])
+        );
+    }
+
+    #[test]
+    fn reports_new_functions_over_budget() {
+        let before = tree("fn a() { if x {} if y {} }");
+        let after = tree("fn a() { if x {} if y {} } fn b() { if x {} } fn c() { a?; b?; }");
+
+        assert_eq!(
+            vec![Addition {
+                file: String::from("lib.rs"),
+                path: String::from("Fn: c"),
+                complexity: 2,
+                budget: 1,
+            }],
+            additions(&[before], &[after], 1)
+        );
+    }
+}
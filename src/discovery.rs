@@ -0,0 +1,250 @@
+//! Module for finding the source files to analyze.
+use crate::analyzer::AnalyzeOptions;
+use std::collections::HashSet;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Options controlling which files are scanned and how they are analyzed.
+#[derive(Debug, Default, Clone)]
+pub struct ScanOptions {
+    pub analyze: AnalyzeOptions,
+    /// Fraction of the discovered files to analyze, between 0.0 and 1.0.
+    /// `None` analyzes every file.
+    pub sample: Option<f64>,
+    /// Seed for the sampling, so the same files are picked across runs.
+    pub seed: u64,
+    /// Directories whose `.rs` files are all analyzed, see `walk`.
+    pub dirs: Vec<String>,
+    /// Glob patterns of files to leave out, see `exclude`.
+    pub exclude: Vec<glob::Pattern>,
+}
+
+/// Expand every pattern into the files it matches, sorted by path.
+///
+/// Patterns without glob metacharacters are kept as they are even when the
+/// file doesn't exist, so the caller gets an error for it instead of the file
+/// silently disappearing from the scan.
+pub fn discover(patterns: &[String]) -> Vec<String> {
+    let mut files: Vec<String> = vec![];
+
+    for pattern in patterns {
+        match glob::glob(pattern) {
+            Ok(paths) if is_glob(pattern) => files.extend(
+                paths
+                    .filter_map(Result::ok)
+                    .map(|path| path.to_string_lossy().into_owned()),
+            ),
+            _ => files.push(pattern.clone()),
+        }
+    }
+
+    files.sort();
+    files.dedup();
+    files
+}
+
+/// Every `.rs` file below `dir`, skipping `target/` directories.
+///
+/// Symlinks are followed, but each directory is only visited once so links
+/// pointing back up the tree don't loop. A `dir` that can't be read is kept
+/// as it is, so the caller gets an error for it.
+pub fn walk(dir: &str) -> Vec<String> {
+    let mut files = vec![];
+    if walk_into(Path::new(dir), &mut HashSet::new(), &mut files).is_err() {
+        files.push(dir.to_string());
+    }
+
+    files.sort();
+    files
+}
+
+fn walk_into(
+    dir: &Path,
+    visited: &mut HashSet<PathBuf>,
+    files: &mut Vec<String>,
+) -> io::Result<()> {
+    if !visited.insert(dir.canonicalize()?) {
+        return Ok(());
+    }
+
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            if path.file_name() != Some(OsStr::new("target")) {
+                // an unreadable subdirectory shouldn't hide the rest of the tree
+                let _ = walk_into(&path, visited, files);
+            }
+        } else if path.extension() == Some(OsStr::new("rs")) {
+            files.push(path.to_string_lossy().into_owned());
+        }
+    }
+
+    Ok(())
+}
+
+/// Drop the files matching any of `excludes`.
+///
+/// Excludes win over everything else, even over a file named explicitly.
+/// `*` doesn't match across `/`, so a pattern like `**/tests/**` is needed
+/// to catch a directory at any depth.
+pub fn exclude(files: Vec<String>, excludes: &[glob::Pattern]) -> Vec<String> {
+    let options = glob::MatchOptions {
+        require_literal_separator: true,
+        ..glob::MatchOptions::new()
+    };
+
+    files
+        .into_iter()
+        .filter(|file| {
+            !excludes
+                .iter()
+                .any(|pattern| pattern.matches_with(file, options))
+        })
+        .collect()
+}
+
+/// Expand `patterns`, add the files of `opts.dirs`, drop the excluded ones
+/// and apply the sampling requested in `opts`.
+pub fn scan(patterns: &[String], opts: &ScanOptions) -> Vec<String> {
+    let mut files = discover(patterns);
+    if !opts.dirs.is_empty() {
+        files.extend(opts.dirs.iter().flat_map(|dir| walk(dir)));
+        files.sort();
+        files.dedup();
+    }
+    let files = exclude(files, &opts.exclude);
+
+    match opts.sample {
+        Some(fraction) => sample(files, fraction, opts.seed),
+        None => files,
+    }
+}
+
+/// Deterministically keep roughly `fraction` of `files`.
+///
+/// Each file is picked based on a hash of its path and `seed` alone, so a
+/// file's selection doesn't depend on which other files were discovered and
+/// stays the same across runs.
+pub fn sample(files: Vec<String>, fraction: f64, seed: u64) -> Vec<String> {
+    let cutoff = (fraction.clamp(0.0, 1.0) * u64::MAX as f64) as u64;
+
+    files
+        .into_iter()
+        .filter(|file| fraction >= 1.0 || fnv1a(seed, file) < cutoff)
+        .collect()
+}
+
+/// FNV-1a, which unlike `DefaultHasher` is stable across Rust releases.
+pub(crate) fn fnv1a(seed: u64, data: &str) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for byte in seed.to_le_bytes().iter().chain(data.as_bytes()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
+
+fn is_glob(pattern: &str) -> bool {
+    pattern.contains(&['*', '?', '['][..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{discover, exclude, sample, walk};
+    use std::fs;
+
+    #[test]
+    fn glob_matches_are_sorted() {
+        let patterns = vec![String::from("tests/fixtures/scan/*.rs")];
+        let should_be = vec![
+            String::from("tests/fixtures/scan/bad.rs"),
+            String::from("tests/fixtures/scan/good.rs"),
+        ];
+
+        assert_eq!(should_be, discover(&patterns));
+    }
+
+    #[test]
+    fn literal_paths_are_kept_even_when_missing() {
+        let patterns = vec![String::from("missing.rs"), String::from("missing.rs")];
+
+        assert_eq!(vec![String::from("missing.rs")], discover(&patterns));
+    }
+
+    #[test]
+    fn walk_finds_rust_files_outside_target() {
+        let dir = std::env::temp_dir().join(format!("walk-{}", std::process::id()));
+        fs::create_dir_all(dir.join("src/nested")).unwrap();
+        fs::create_dir_all(dir.join("target/debug")).unwrap();
+        for file in &["src/lib.rs", "src/nested/mod.rs", "target/debug/out.rs"] {
+            fs::write(dir.join(file), "").unwrap();
+        }
+        fs::write(dir.join("src/notes.txt"), "").unwrap();
+        #[cfg(unix)]
+        std::os::unix::fs::symlink(&dir, dir.join("src/nested/loop")).unwrap();
+
+        let root = dir.to_str().unwrap();
+        assert_eq!(
+            vec![
+                format!("{}/src/lib.rs", root),
+                format!("{}/src/nested/mod.rs", root),
+            ],
+            walk(root)
+        );
+
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn walk_keeps_unreadable_dirs() {
+        assert_eq!(vec![String::from("missing")], walk("missing"));
+    }
+
+    #[test]
+    fn excludes_match_whole_path_components() {
+        let files = vec![
+            String::from("src/lib.rs"),
+            String::from("src/parser_generated.rs"),
+            String::from("tests/cli.rs"),
+            String::from("src/nested/tests/mod.rs"),
+            String::from("src/tests.rs"),
+        ];
+        let excludes = vec![
+            glob::Pattern::new("**/tests/**").unwrap(),
+            glob::Pattern::new("**/*_generated.rs").unwrap(),
+        ];
+
+        assert_eq!(
+            vec![String::from("src/lib.rs"), String::from("src/tests.rs")],
+            exclude(files, &excludes)
+        );
+    }
+
+    #[test]
+    fn sampling_is_deterministic() {
+        let files: Vec<String> = (0..10_000).map(|i| format!("src/file_{}.rs", i)).collect();
+
+        let first = sample(files.clone(), 0.3, 42);
+        let second = sample(files.clone(), 0.3, 42);
+        assert_eq!(first, second);
+        assert!(
+            (2_700..3_300).contains(&first.len()),
+            "sampled {} files",
+            first.len()
+        );
+
+        assert_ne!(first, sample(files, 0.3, 7));
+    }
+
+    #[test]
+    fn sampling_extremes() {
+        let files: Vec<String> = (0..100).map(|i| format!("src/file_{}.rs", i)).collect();
+
+        assert!(sample(files.clone(), 0.0, 0).is_empty());
+        assert_eq!(files, sample(files.clone(), 1.0, 0));
+    }
+}
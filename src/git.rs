@@ -0,0 +1,60 @@
+//! Module for reading previous revisions of files through `git`.
+use std::fs;
+use std::io;
+use std::process::Command;
+
+/// The commit where HEAD diverged from `base`.
+pub fn merge_base(base: &str) -> io::Result<String> {
+    let output = git(&["merge-base", "HEAD", base])?;
+
+    Ok(output.trim().to_string())
+}
+
+/// Contents of `path` (relative to the current directory, or absolute) at
+/// `rev`, or `None` if the file didn't exist there. Paths outside the
+/// repository are an error.
+pub fn show(rev: &str, path: &str) -> io::Result<Option<String>> {
+    let spec = format!("{}:{}", rev, repo_path(path)?);
+    let output = Command::new("git").args(["show", &spec]).output()?;
+
+    if output.status.success() {
+        Ok(Some(String::from_utf8_lossy(&output.stdout).into_owned()))
+    } else {
+        Ok(None)
+    }
+}
+
+/// `path` relative to the top of the work tree, with `/` between the
+/// components whatever the platform, as `rev:path` needs it.
+fn repo_path(path: &str) -> io::Result<String> {
+    let top = fs::canonicalize(git(&["rev-parse", "--show-toplevel"])?.trim())?;
+    let absolute = fs::canonicalize(path)
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path, err)))?;
+
+    match absolute.strip_prefix(&top) {
+        Ok(relative) => Ok(relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")),
+        Err(_) => Err(io::Error::other(format!(
+            "{} is outside the git repository at {}",
+            path,
+            top.display()
+        ))),
+    }
+}
+
+fn git(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("git").args(args).output()?;
+
+    if output.status.success() {
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        Err(io::Error::other(format!(
+            "git {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr).trim()
+        )))
+    }
+}
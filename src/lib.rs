@@ -1,6 +1,16 @@
-#![feature(exclusive_range_pattern)]
-
+pub mod analyzer;
+pub mod cache;
 pub mod calculator;
 pub mod config;
+pub mod diff;
+pub mod discovery;
+pub mod git;
 pub mod parsers;
+pub mod report;
+pub mod stats;
 pub mod thread_pool;
+
+/// Result of analyzing a source; match on `ParseError::kind` to tell I/O,
+/// syntax and other failures apart.
+pub type Result<T> = parsers::error::ParseResult<T>;
+pub use parsers::error::{ParseError, ParseErrorKind};
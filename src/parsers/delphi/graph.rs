@@ -0,0 +1,699 @@
+//! Module building the control-flow graphs of Delphi routines from tokens.
+use crate::analyzer::DEFAULT_MAX_DEPTH;
+use crate::calculator::{Edge, Graph, Node, Parser};
+use crate::parsers::delphi::lexer::{self, Keyword, Span, TokenKind};
+use crate::parsers::error::{ParseError, ParseErrorKind, ParseResult};
+use crate::parsers::rust_parser::{ComplexityNode, ComplexityNodeKind, ComplexityTree};
+
+use std::fs;
+
+/// Directives that may follow a routine header, e.g. `procedure Foo; inline;`.
+const DIRECTIVES: &[&str] = &[
+    "abstract",
+    "assembler",
+    "cdecl",
+    "deprecated",
+    "dispid",
+    "dynamic",
+    "external",
+    "final",
+    "forward",
+    "inline",
+    "message",
+    "overload",
+    "override",
+    "pascal",
+    "platform",
+    "register",
+    "reintroduce",
+    "safecall",
+    "static",
+    "stdcall",
+    "varargs",
+    "virtual",
+];
+
+/// A procedure, function, constructor or destructor that has a body.
+pub struct Routine {
+    /// The name as declared, e.g. `TFoo.Bar` for a method.
+    pub name: String,
+    pub line: usize,
+    pub column: usize,
+    /// Lines from the name through the `end` of the body.
+    pub loc: usize,
+    pub graph: Graph,
+}
+
+/// Builds the control-flow graph of Delphi units and programs (`.pas`,
+/// `.dpr`), with one node per statement.
+#[derive(Debug, Default)]
+pub struct DelphiGraphParser;
+
+impl Parser for DelphiGraphParser {
+    /// Every routine of `file` as a disconnected component of one graph, so
+    /// the complexity of the graph is the sum of theirs.
+    fn parse(&mut self, file: String) -> ParseResult<Graph> {
+        let src = fs::read_to_string(file)?;
+
+        Ok(Graph::new(
+            routines(&src, DEFAULT_MAX_DEPTH)?
+                .into_iter()
+                .flat_map(|routine| routine.graph.edges)
+                .collect(),
+        ))
+    }
+}
+
+/// Every routine with a body in `src`, in source order but for nested routines,
+/// which come before the routine they are nested in. Declarations without
+/// a body (`forward`, `external`, interface sections, class types) are
+/// skipped.
+///
+/// Statements nested more than `max_depth` deep are an error rather than a
+/// stack overflow.
+pub fn routines(src: &str, max_depth: usize) -> ParseResult<Vec<Routine>> {
+    let tokens = lexer::tokenize(src)?;
+    let mut builder = Builder::new(&tokens, max_depth);
+    let mut routines = vec![];
+
+    while builder.pos < tokens.len() {
+        if builder.at_routine() {
+            builder.routine(&mut routines)?;
+        } else {
+            builder.pos += 1;
+        }
+    }
+
+    Ok(routines)
+}
+
+/// Analyze Delphi source, with a `Fn` node for each routine. `name` becomes
+/// the name of the root node.
+///
+/// Like for Rust, a routine's complexity counts its decisions, so a routine
+/// without any is 0. Delphi visibility isn't tracked, so every routine is
+/// public.
+pub fn tree(name: String, src: &str, max_depth: usize) -> ParseResult<ComplexityTree> {
+    let mut root = ComplexityNode::new(name, ComplexityNodeKind::File);
+    for routine in routines(src, max_depth)? {
+        let complexity = routine.graph.calculate_complexity().max(1) as usize - 1;
+        root.add_child(
+            ComplexityNode::new(routine.name, ComplexityNodeKind::Fn)
+                .with_complexity(complexity)
+                .with_position(routine.line, routine.column)
+                .with_loc(routine.loc)
+                .with_public(true),
+        );
+    }
+
+    Ok(ComplexityTree { root })
+}
+
+struct Builder<'a> {
+    tokens: &'a [(TokenKind, Span)],
+    pos: usize,
+    next_node: Node,
+    edges: Vec<Edge>,
+    /// How many statements enclose the current one.
+    depth: usize,
+    max_depth: usize,
+}
+
+impl<'a> Builder<'a> {
+    fn new(tokens: &'a [(TokenKind, Span)], max_depth: usize) -> Builder<'a> {
+        Builder {
+            tokens,
+            pos: 0,
+            next_node: 0,
+            edges: vec![],
+            depth: 0,
+            max_depth,
+        }
+    }
+
+    fn peek(&self) -> Option<&TokenKind> {
+        self.tokens.get(self.pos).map(|(token, _)| token)
+    }
+
+    fn at(&self, keyword: Keyword) -> bool {
+        self.peek() == Some(&TokenKind::Keyword(keyword))
+    }
+
+    /// Whether the next token is the (non reserved) word `word`.
+    fn at_word(&self, word: &str) -> bool {
+        match self.peek() {
+            Some(TokenKind::Identifier(ident)) => ident.eq_ignore_ascii_case(word),
+            _ => false,
+        }
+    }
+
+    fn at_routine(&self) -> bool {
+        matches!(
+            self.peek(),
+            Some(TokenKind::Keyword(Keyword::Procedure))
+                | Some(TokenKind::Keyword(Keyword::Function))
+                | Some(TokenKind::Keyword(Keyword::Constructor))
+                | Some(TokenKind::Keyword(Keyword::Destructor))
+        )
+    }
+
+    /// Whether the next token ends a statement sequence, for whichever
+    /// construct encloses it to check.
+    fn at_sequence_end(&self) -> bool {
+        matches!(
+            self.peek(),
+            None | Some(TokenKind::Keyword(Keyword::End))
+                | Some(TokenKind::Keyword(Keyword::Until))
+                | Some(TokenKind::Keyword(Keyword::Else))
+                | Some(TokenKind::Keyword(Keyword::Except))
+                | Some(TokenKind::Keyword(Keyword::Finally))
+        )
+    }
+
+    fn expect(&mut self, keyword: Keyword) -> ParseResult<()> {
+        if self.at(keyword) {
+            self.pos += 1;
+            Ok(())
+        } else {
+            Err(self.error(format!("Expected {:?}", keyword)))
+        }
+    }
+
+    /// An `InvalidSymbol` error at the next token, or `UnexpectedEOF`.
+    fn error(&self, msg: String) -> ParseError {
+        match self.tokens.get(self.pos) {
+            Some((_, span)) => ParseError::kind(ParseErrorKind::InvalidSymbol)
+                .msg(msg)
+                .index(span.start)
+                .position(span.line, span.column),
+            None => ParseError::kind(ParseErrorKind::UnexpectedEOF).msg(msg),
+        }
+    }
+
+    fn node(&mut self) -> Node {
+        self.next_node += 1;
+        self.next_node
+    }
+
+    fn edge(&mut self, from: Node, to: Node) {
+        self.edges.push(Edge::from((from, to)));
+    }
+
+    /// Skip tokens, outside of brackets, until `is_end` holds for the next
+    /// token or the tokens run out.
+    fn skip_until<F>(&mut self, is_end: F)
+    where
+        F: Fn(&Builder) -> bool,
+    {
+        let mut depth = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                TokenKind::OpenParen | TokenKind::OpenSquare => depth += 1,
+                TokenKind::CloseParen | TokenKind::CloseSquare if depth > 0 => depth -= 1,
+                _ if depth == 0 && is_end(self) => return,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Skip an expression up to and including `keyword`, e.g. the condition
+    /// of an `if` up to its `then`.
+    fn skip_past(&mut self, keyword: Keyword) -> ParseResult<()> {
+        self.skip_until(|builder| builder.at(keyword));
+        self.expect(keyword)
+    }
+
+    /// Parse the routine at the next token, adding it to `routines` if it has
+    /// a body, along with the routines nested in it.
+    fn routine(&mut self, routines: &mut Vec<Routine>) -> ParseResult<()> {
+        self.pos += 1;
+        let (line, column) = match self.tokens.get(self.pos) {
+            Some((_, span)) => (span.line, span.column),
+            None => return Ok(()),
+        };
+        let name = self.routine_name();
+        self.skip_until(|builder| builder.peek() == Some(&TokenKind::Semicolon));
+        self.pos += 1;
+        // procedural types, e.g. `TEvent = procedure(Sender: TObject) of object;`
+        if name.is_empty() {
+            return Ok(());
+        }
+
+        while let Some(TokenKind::Identifier(ident)) = self.peek() {
+            let ident = ident.to_ascii_lowercase();
+            if !DIRECTIVES.contains(&ident.as_str()) {
+                break;
+            }
+            self.skip_until(|builder| builder.peek() == Some(&TokenKind::Semicolon));
+            self.pos += 1;
+            if ident == "forward" || ident == "external" {
+                return Ok(());
+            }
+        }
+
+        loop {
+            match self.peek() {
+                Some(TokenKind::Keyword(Keyword::Begin)) => break,
+                // after the body of a nested routine
+                Some(TokenKind::Semicolon) => self.pos += 1,
+                Some(TokenKind::Keyword(Keyword::Var))
+                | Some(TokenKind::Keyword(Keyword::Const))
+                | Some(TokenKind::Keyword(Keyword::Type))
+                | Some(TokenKind::Keyword(Keyword::Label)) => self.declarations(),
+                _ if self.at_routine() => self.routine(routines)?,
+                // a declaration, e.g. in an interface section or class type
+                _ => return Ok(()),
+            }
+        }
+
+        // the graph of an empty body still needs an edge for its one path
+        let edges = std::mem::take(&mut self.edges);
+        let entry = self.node();
+        let body = self.statement(entry)?;
+        let exit = self.node();
+        self.edge(body, exit);
+        let graph = Graph::new(std::mem::replace(&mut self.edges, edges));
+        let end = self.tokens[self.pos - 1].1.line;
+        routines.push(Routine {
+            name,
+            line,
+            column,
+            loc: end + 1 - line,
+            graph,
+        });
+
+        Ok(())
+    }
+
+    /// `TFoo.Bar` for `TFoo<T>.Bar`, leaving generic parameters out.
+    fn routine_name(&mut self) -> String {
+        let mut name = String::new();
+        let mut generics = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                TokenKind::LessThan => generics += 1,
+                TokenKind::GreaterThan => generics -= 1,
+                _ if generics > 0 => {}
+                TokenKind::Identifier(ident) => name.push_str(ident),
+                TokenKind::Dot => name.push('.'),
+                _ => break,
+            }
+            self.pos += 1;
+        }
+
+        name
+    }
+
+    /// Skip a `var`, `const`, `type` or `label` section of a routine.
+    fn declarations(&mut self) {
+        self.pos += 1;
+        let mut records = 0;
+        while let Some(token) = self.peek() {
+            match token {
+                TokenKind::Keyword(Keyword::Record) => records += 1,
+                TokenKind::Keyword(Keyword::End) if records > 0 => records -= 1,
+                TokenKind::Keyword(Keyword::Begin)
+                | TokenKind::Keyword(Keyword::Var)
+                | TokenKind::Keyword(Keyword::Const)
+                | TokenKind::Keyword(Keyword::Type)
+                | TokenKind::Keyword(Keyword::Label)
+                    if records == 0 =>
+                {
+                    return
+                }
+                _ if records == 0 && self.at_routine() => return,
+                _ => {}
+            }
+            self.pos += 1;
+        }
+    }
+
+    /// Add the statement at the next token to the graph, entered from
+    /// `entry`, and return the node control leaves it from.
+    fn statement(&mut self, entry: Node) -> ParseResult<Node> {
+        // every construct nests through here, so this bounds the recursion
+        if self.depth == self.max_depth {
+            return Err(self.error(format!("statements nested deeper than {}", self.max_depth)));
+        }
+
+        self.depth += 1;
+        let exit = self.nested_statement(entry);
+        self.depth -= 1;
+        exit
+    }
+
+    fn nested_statement(&mut self, entry: Node) -> ParseResult<Node> {
+        match self.peek() {
+            Some(TokenKind::Keyword(Keyword::Begin)) => {
+                self.pos += 1;
+                let exit = self.sequence(entry)?;
+                self.expect(Keyword::End)?;
+                Ok(exit)
+            }
+            Some(TokenKind::Keyword(Keyword::If)) => self.if_statement(entry),
+            Some(TokenKind::Keyword(Keyword::Case)) => self.case_statement(entry),
+            Some(TokenKind::Keyword(Keyword::While)) | Some(TokenKind::Keyword(Keyword::For)) => {
+                self.pos += 1;
+                let condition = self.node();
+                self.edge(entry, condition);
+                self.skip_past(Keyword::Do)?;
+                let body = self.statement(condition)?;
+                self.edge(body, condition);
+
+                let after = self.node();
+                self.edge(condition, after);
+                Ok(after)
+            }
+            Some(TokenKind::Keyword(Keyword::Repeat)) => {
+                self.pos += 1;
+                let head = self.node();
+                self.edge(entry, head);
+                let body = self.sequence(head)?;
+                self.expect(Keyword::Until)?;
+
+                let condition = self.simple_statement(body);
+                self.edge(condition, head);
+                let after = self.node();
+                self.edge(condition, after);
+                Ok(after)
+            }
+            Some(TokenKind::Keyword(Keyword::Try)) => self.try_statement(entry),
+            Some(TokenKind::Keyword(Keyword::With)) => {
+                self.pos += 1;
+                let with = self.node();
+                self.edge(entry, with);
+                self.skip_past(Keyword::Do)?;
+                self.statement(with)
+            }
+            Some(TokenKind::Keyword(Keyword::Asm)) => {
+                self.skip_until(|builder| builder.at(Keyword::End));
+                self.expect(Keyword::End)?;
+                let asm = self.node();
+                self.edge(entry, asm);
+                Ok(asm)
+            }
+            _ => Ok(self.simple_statement(entry)),
+        }
+    }
+
+    /// An assignment, call, `exit` and the like, up to the `;` or the end of
+    /// the enclosing construct.
+    fn simple_statement(&mut self, entry: Node) -> Node {
+        self.skip_until(|builder| {
+            builder.peek() == Some(&TokenKind::Semicolon) || builder.at_sequence_end()
+        });
+
+        let node = self.node();
+        self.edge(entry, node);
+        node
+    }
+
+    /// Statements separated by `;`, up to the end of the enclosing construct.
+    fn sequence(&mut self, entry: Node) -> ParseResult<Node> {
+        let mut exit = entry;
+        loop {
+            while self.peek() == Some(&TokenKind::Semicolon) {
+                self.pos += 1;
+            }
+            if self.at_sequence_end() {
+                return Ok(exit);
+            }
+            exit = self.statement(exit)?;
+        }
+    }
+
+    fn if_statement(&mut self, entry: Node) -> ParseResult<Node> {
+        self.pos += 1;
+        let condition = self.node();
+        self.edge(entry, condition);
+        self.skip_past(Keyword::Then)?;
+
+        let then = self.statement(condition)?;
+        let otherwise = if self.at(Keyword::Else) {
+            self.pos += 1;
+            self.statement(condition)?
+        } else {
+            condition
+        };
+
+        let join = self.node();
+        self.edge(then, join);
+        self.edge(otherwise, join);
+        Ok(join)
+    }
+
+    /// Each arm is a branch, as is falling through without an `else`.
+    fn case_statement(&mut self, entry: Node) -> ParseResult<Node> {
+        self.pos += 1;
+        let selector = self.node();
+        self.edge(entry, selector);
+        self.skip_past(Keyword::Of)?;
+
+        let mut arms = vec![];
+        loop {
+            while self.peek() == Some(&TokenKind::Semicolon) {
+                self.pos += 1;
+            }
+            if self.at_sequence_end() {
+                break;
+            }
+            self.skip_until(|builder| builder.peek() == Some(&TokenKind::Colon));
+            self.pos += 1;
+            arms.push(self.statement(selector)?);
+        }
+
+        if self.at(Keyword::Else) {
+            self.pos += 1;
+            arms.push(self.sequence(selector)?);
+        } else {
+            arms.push(selector);
+        }
+        self.expect(Keyword::End)?;
+
+        let join = self.node();
+        for arm in arms {
+            self.edge(arm, join);
+        }
+        Ok(join)
+    }
+
+    /// `finally` blocks always run after the body. `except` blocks are a
+    /// branch of their own, or one branch per `on E: Exception do` handler.
+    fn try_statement(&mut self, entry: Node) -> ParseResult<Node> {
+        self.pos += 1;
+        let head = self.node();
+        self.edge(entry, head);
+        let body = self.sequence(head)?;
+
+        let exit = if self.at(Keyword::Finally) {
+            self.pos += 1;
+            self.sequence(body)?
+        } else {
+            self.expect(Keyword::Except)?;
+            let mut handlers = vec![body];
+            if self.at_word("on") {
+                while self.at_word("on") {
+                    self.skip_past(Keyword::Do)?;
+                    handlers.push(self.statement(head)?);
+                    while self.peek() == Some(&TokenKind::Semicolon) {
+                        self.pos += 1;
+                    }
+                }
+                if self.at(Keyword::Else) {
+                    self.pos += 1;
+                    handlers.push(self.sequence(head)?);
+                }
+            } else {
+                handlers.push(self.sequence(head)?);
+            }
+
+            let join = self.node();
+            for handler in handlers {
+                self.edge(handler, join);
+            }
+            join
+        };
+        self.expect(Keyword::End)?;
+
+        Ok(exit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{routines, tree, DelphiGraphParser};
+    use crate::analyzer::DEFAULT_MAX_DEPTH;
+    use crate::calculator::{self, Edge, Graph, Parser};
+    use crate::parsers::error::ParseErrorKind;
+    use rstest::rstest;
+
+    fn complexity(body: &str) -> usize {
+        let src = format!("procedure Foo;\nbegin\n{}\nend;", body);
+        let tree = tree(String::from("test.pas"), &src, DEFAULT_MAX_DEPTH).unwrap();
+
+        assert_eq!(1, tree.root.children.len());
+        tree.root.children[0].complexity
+    }
+
+    #[rstest]
+    #[case("", 0)]
+    #[case("a := 1; b := 2; Foo(a, b);", 0)]
+    #[case("if a then b := 1;", 1)]
+    #[case("if a then b := 1 else b := 2;", 1)]
+    #[case("if a then if b then c else d else e;", 2)]
+    #[case("while a < 10 do begin Inc(a); if a = 5 then Break; end;", 2)]
+    #[case("for i := 0 to 9 do Foo(i);", 1)]
+    #[case("repeat Inc(a); until a > 10;", 1)]
+    #[case("case a of 1: b; 2, 3: c; 4..5: begin d; e end; end;", 3)]
+    #[case("case a of 1: b; 2: c else d; e; end;", 2)]
+    #[case("try a; finally b; end;", 0)]
+    #[case("try a; except b; end;", 1)]
+    #[case("try a; except on E: EFoo do b; on EBar do c; else d; end;", 3)]
+    #[case("with Foo do if a then b;", 1)]
+    #[case("asm mov eax, 1 end;", 0)]
+    fn statements(#[case] body: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(body));
+    }
+
+    #[test]
+    fn routines_are_found_with_their_position() {
+        let src = "
+            unit Foo;
+            interface
+            procedure Declared;
+            type
+              TFoo = class
+                function Bar(a: Integer; b: Integer): Boolean; virtual;
+                property Baz: Integer read FBaz;
+              end;
+            implementation
+            procedure Later; forward;
+            function TFoo.Bar(a: Integer; b: Integer): Boolean;
+            var
+              r: record x: Integer; end;
+              procedure Nested;
+              begin
+                if a > b then Exit;
+              end;
+            begin
+              Result := a < b;
+            end;
+            constructor TList<T>.Create;
+            begin
+            end;
+            end.
+        ";
+        let tree = tree(String::from("foo.pas"), src, DEFAULT_MAX_DEPTH).unwrap();
+        let routines: Vec<(&str, usize, usize, usize)> = tree
+            .root
+            .children
+            .iter()
+            .map(|node| (node.name.as_str(), node.complexity, node.line, node.column))
+            .collect();
+
+        assert_eq!(
+            vec![
+                ("Nested", 1, 15, 25),
+                ("TFoo.Bar", 0, 12, 22),
+                ("TList.Create", 0, 22, 25),
+            ],
+            routines
+        );
+
+        let loc: Vec<usize> = tree.root.children.iter().map(|node| node.loc).collect();
+        assert_eq!(vec![4, 10, 3], loc);
+    }
+
+    #[test]
+    fn unbalanced_blocks_are_errors() {
+        let err = routines(
+            "procedure Foo;\nbegin\n  if a then\nuntil;",
+            DEFAULT_MAX_DEPTH,
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(ParseErrorKind::InvalidSymbol, err.kind);
+        assert_eq!(Some((4, 1)), err.position);
+    }
+
+    #[test]
+    fn deep_nesting_is_an_error_instead_of_overflowing() {
+        let nested = |depth: usize| {
+            format!(
+                "procedure Foo;\nbegin\n{} a := 1 {}\nend;",
+                "if a then begin ".repeat(depth),
+                "end ".repeat(depth)
+            )
+        };
+
+        let err = routines(&nested(100_000), 64).err().unwrap();
+        assert_eq!(ParseErrorKind::InvalidSymbol, err.kind);
+        assert_eq!(
+            Some(String::from("statements nested deeper than 64")),
+            err.msg
+        );
+
+        assert_eq!(
+            31,
+            tree(String::from("test.pas"), &nested(31), 64)
+                .unwrap()
+                .root
+                .children[0]
+                .complexity
+        );
+    }
+
+    #[test]
+    fn each_routine_is_a_component_of_the_file_graph() {
+        let file = "tests/fixtures/delphi/unit.pas";
+        // Sign: entry, condition, then, else, join, exit
+        // Count: entry, condition, body, after, exit
+        let by_hand = Graph::new(
+            vec![
+                (1, 2),
+                (2, 3),
+                (2, 4),
+                (3, 5),
+                (4, 5),
+                (5, 6),
+                (7, 8),
+                (8, 9),
+                (9, 8),
+                (8, 10),
+                (10, 11),
+            ]
+            .into_iter()
+            .map(Edge::from)
+            .collect(),
+        );
+        let graph = DelphiGraphParser.parse(String::from(file)).unwrap();
+        assert_eq!(by_hand.to_dot(), graph.to_dot());
+
+        // E - N + P + X = 11 - 11 + 2 + 2
+        assert_eq!(4, graph.calculate_complexity());
+    }
+
+    #[test]
+    fn parser_fails_on_files_it_cannot_read() {
+        let err = calculator::calculate(
+            String::from("tests/fixtures/delphi/missing.pas"),
+            DelphiGraphParser,
+        )
+        .err()
+        .unwrap();
+
+        assert_eq!(ParseErrorKind::Io, err.kind);
+    }
+
+    #[test]
+    fn parser_sums_the_routines_of_a_file() {
+        let file = "tests/fixtures/delphi/unit.pas";
+
+        assert_eq!(
+            4,
+            calculator::calculate(String::from(file), DelphiGraphParser).unwrap()
+        );
+    }
+}
@@ -6,16 +6,21 @@ pub enum TokenKind {
     Integer(usize),
     Decimal(f64),
     Identifier(String),
+    Keyword(Keyword),
     QuotedString(String),
+    CharCode(u32),
     Asterisk,
     At,
     Carat,
     CloseParen,
     CloseSquare,
     Colon,
+    Comma,
     Dot,
     End,
     Equals,
+    GreaterThan,
+    LessThan,
     Minus,
     OpenParen,
     OpenSquare,
@@ -24,6 +29,77 @@ pub enum TokenKind {
     Slash,
 }
 
+/// Reserved words, which Delphi matches regardless of case.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Keyword {
+    Begin,
+    End,
+    If,
+    Then,
+    Else,
+    While,
+    Do,
+    For,
+    Case,
+    Repeat,
+    Until,
+    Function,
+    Procedure,
+    Constructor,
+    Destructor,
+    Of,
+    Try,
+    Except,
+    Finally,
+    With,
+    Asm,
+    Var,
+    Const,
+    Type,
+    Label,
+    Record,
+}
+
+impl Keyword {
+    fn lookup(word: &str) -> Option<Keyword> {
+        Some(match word.to_ascii_lowercase().as_str() {
+            "begin" => Keyword::Begin,
+            "end" => Keyword::End,
+            "if" => Keyword::If,
+            "then" => Keyword::Then,
+            "else" => Keyword::Else,
+            "while" => Keyword::While,
+            "do" => Keyword::Do,
+            "for" => Keyword::For,
+            "case" => Keyword::Case,
+            "repeat" => Keyword::Repeat,
+            "until" => Keyword::Until,
+            "function" => Keyword::Function,
+            "procedure" => Keyword::Procedure,
+            "constructor" => Keyword::Constructor,
+            "destructor" => Keyword::Destructor,
+            "of" => Keyword::Of,
+            "try" => Keyword::Try,
+            "except" => Keyword::Except,
+            "finally" => Keyword::Finally,
+            "with" => Keyword::With,
+            "asm" => Keyword::Asm,
+            "var" => Keyword::Var,
+            "const" => Keyword::Const,
+            "type" => Keyword::Type,
+            "label" => Keyword::Label,
+            "record" => Keyword::Record,
+            _ => return None,
+        })
+    }
+}
+
+impl From<Keyword> for TokenKind {
+    fn from(other: Keyword) -> TokenKind {
+        TokenKind::Keyword(other)
+    }
+}
+
 impl From<String> for TokenKind {
     fn from(other: String) -> TokenKind {
         TokenKind::Identifier(other)
@@ -48,40 +124,68 @@ impl From<f64> for TokenKind {
     }
 }
 
+/// Where a token is in the source: the half-open byte interval `[start, end)`
+/// and the 1-based line and column it starts at.
+///
+/// Columns count characters, not bytes, and `\r\n` is a single line break.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Span {
+        Span {
+            start,
+            end,
+            line,
+            column,
+        }
+    }
+}
+
 struct Tokenizer<'a> {
     cur_idx: usize,
+    line: usize,
+    column: usize,
     data: &'a str,
 }
 
 impl<'a> Tokenizer<'a> {
-    fn new(data: &str) -> Tokenizer {
-        Tokenizer { cur_idx: 0, data }
+    fn new(data: &'a str) -> Tokenizer<'a> {
+        Tokenizer {
+            cur_idx: 0,
+            line: 1,
+            column: 1,
+            data,
+        }
     }
 
-    fn next_token(&mut self) -> ParseResult<Option<(TokenKind, usize, usize)>> {
+    fn next_token(&mut self) -> ParseResult<Option<(TokenKind, Span)>> {
         self.skip_whitespace();
 
         if self.data.is_empty() {
             Ok(None)
         } else {
-            let start = self.cur_idx;
+            let (start, line, column) = (self.cur_idx, self.line, self.column);
             let token = self.fetch_next_token()?;
             let end = self.cur_idx;
 
-            Ok(Some((token, start, end)))
+            Ok(Some((token, Span::new(start, end, line, column))))
         }
     }
 
     fn fetch_next_token(&mut self) -> ParseResult<TokenKind> {
-        let response = tokenize_next_token(self.data);
-
-        if response.is_err() {
-            Err(response.unwrap_err().index(self.cur_idx))
-        } else {
-            let (token, bytes_read) = response.unwrap();
-            self.chomp(bytes_read);
+        match tokenize_next_token(self.data) {
+            Ok((token, bytes_read)) => {
+                self.chomp(bytes_read);
 
-            Ok(token)
+                Ok(token)
+            }
+            Err(err) => Err(err.index(self.cur_idx).position(self.line, self.column)),
         }
     }
 
@@ -90,17 +194,26 @@ impl<'a> Tokenizer<'a> {
     }
 
     fn chomp(&mut self, num_bytes: usize) {
+        for (i, ch) in self.data[..num_bytes].char_indices() {
+            match ch {
+                // the `\n` of a `\r\n` ends the line
+                '\r' if self.data[i + 1..].starts_with('\n') => {}
+                '\n' | '\r' => {
+                    self.line += 1;
+                    self.column = 1;
+                }
+                _ => self.column += 1,
+            }
+        }
+
         self.data = &self.data[num_bytes..];
         self.cur_idx += num_bytes;
     }
 }
 
 /// Turn a string of valid Delphi code into a list of tokens, including the
-/// location of that token's start and end point in the original source code.
-///
-/// Note the token indices represent the half-open interval `[start, end)`,
-/// equivalent to `start .. end` in Rust.
-pub fn tokenize(data: &str) -> ParseResult<Vec<(TokenKind, usize, usize)>> {
+/// `Span` of each token in the original source code.
+pub fn tokenize(data: &str) -> ParseResult<Vec<(TokenKind, Span)>> {
     let mut tokenizer = Tokenizer::new(data);
     let mut tokens = vec![];
 
@@ -132,7 +245,13 @@ fn tokenize_next_token(data: &str) -> ParseResult<(TokenKind, usize)> {
         ']' => (TokenKind::CloseSquare, 1),
         ':' => (TokenKind::Colon, 1),
         ';' => (TokenKind::Semicolon, 1),
-        '0'..'9' => tokenize_number(data)?,
+        ',' => (TokenKind::Comma, 1),
+        '<' => (TokenKind::LessThan, 1),
+        '>' => (TokenKind::GreaterThan, 1),
+        '\'' => tokenize_string(data)?,
+        '#' => tokenize_char_code(data)?,
+        '$' => tokenize_hex(data)?,
+        '0'..='9' => tokenize_number(data)?,
         c @ '_' | c if c.is_alphabetic() => tokenize_identifier(data)?,
         other => return Err(ParseError::kind(ParseErrorKind::UnknownCharacter(other))),
     })
@@ -143,15 +262,18 @@ fn tokenize_identifier(data: &str) -> ParseResult<(TokenKind, usize)> {
 
     let (got, bytes_read) = take_while(data, |ch| ch == '_' || ch.is_alphanumeric())?;
 
-    // TODO: Recognise keywords using a `match` statement here.
+    let token = match Keyword::lookup(got) {
+        Some(keyword) => TokenKind::Keyword(keyword),
+        None => TokenKind::Identifier(got.to_string()),
+    };
 
-    Ok((TokenKind::Identifier(got.to_string()), bytes_read))
+    Ok((token, bytes_read))
 }
 
 fn validate_idenifier_char(ch: Option<char>) -> ParseResult<()> {
     match ch {
         Some(ch) => {
-            if ch.is_digit(10) {
+            if ch.is_ascii_digit() {
                 Err(ParseError::kind(ParseErrorKind::InvalidSymbol)
                     .msg("Identifiers can't start with numbers".to_string()))
             } else {
@@ -163,11 +285,11 @@ fn validate_idenifier_char(ch: Option<char>) -> ParseResult<()> {
     }
 }
 
-/// Tokenize a numeric literal.
+/// Tokenize a numeric literal, optionally with an exponent (`1.5e10`).
 fn tokenize_number(data: &str) -> ParseResult<(TokenKind, usize)> {
     let mut seen_dot = false;
     let (decimal, bytes_read) = take_while(data, |c| {
-        if c.is_digit(10) {
+        if c.is_ascii_digit() {
             true
         } else if c == '.' {
             if !seen_dot {
@@ -181,13 +303,85 @@ fn tokenize_number(data: &str) -> ParseResult<(TokenKind, usize)> {
         }
     })?;
 
-    if seen_dot {
+    let exponent = exponent_len(&data[bytes_read..]);
+    if exponent > 0 {
+        let bytes_read = bytes_read + exponent;
+        Ok((
+            TokenKind::Decimal(data[..bytes_read].parse::<f64>()?),
+            bytes_read,
+        ))
+    } else if seen_dot {
         Ok((TokenKind::Decimal(decimal.parse::<f64>()?), bytes_read))
     } else {
         Ok((TokenKind::Integer(decimal.parse::<usize>()?), bytes_read))
     }
 }
 
+/// Length of the exponent at the start of `data`: an `e` or `E`, an optional
+/// sign and its digits. A missing digit is left for float parsing to reject.
+fn exponent_len(data: &str) -> usize {
+    let rest = match data.strip_prefix(|c| c == 'e' || c == 'E') {
+        Some(rest) => rest,
+        None => return 0,
+    };
+    let unsigned = rest.strip_prefix(|c| c == '+' || c == '-').unwrap_or(rest);
+    let digits = take_while(unsigned, |c| c.is_ascii_digit()).map_or(0, |(_, len)| len);
+
+    data.len() - unsigned.len() + digits
+}
+
+/// Tokenize a hex integer literal such as `$1F`.
+fn tokenize_hex(data: &str) -> ParseResult<(TokenKind, usize)> {
+    match take_while(&data[1..], |c| c.is_ascii_hexdigit()) {
+        Ok((digits, bytes_read)) => Ok((
+            TokenKind::Integer(usize::from_str_radix(digits, 16)?),
+            bytes_read + 1,
+        )),
+        Err(_) => Err(ParseError::kind(ParseErrorKind::UnknownCharacter('$'))),
+    }
+}
+
+/// Tokenize a single-quoted string literal, where a doubled quote (`''`)
+/// stands for a literal quote.
+fn tokenize_string(data: &str) -> ParseResult<(TokenKind, usize)> {
+    let mut got = String::new();
+    let mut rest = &data[1..];
+
+    while let Some(end) = rest.find('\'') {
+        got.push_str(&rest[..end]);
+        rest = &rest[end + 1..];
+
+        if rest.starts_with('\'') {
+            got.push('\'');
+            rest = &rest[1..];
+        } else {
+            return Ok((TokenKind::QuotedString(got), data.len() - rest.len()));
+        }
+    }
+
+    Err(ParseError::kind(ParseErrorKind::UnexpectedEOF)
+        .msg("Unterminated string literal".to_string()))
+}
+
+/// Tokenize a character code such as `#13` or `#$0D`.
+///
+/// Delphi strings are frequently built by juxtaposing quoted strings and
+/// character codes (`'a'#13#10'b'`), so each part is emitted as its own token.
+fn tokenize_char_code(data: &str) -> ParseResult<(TokenKind, usize)> {
+    let (code, bytes_read) = match data[1..].strip_prefix('$') {
+        Some(hex) => {
+            let (digits, bytes_read) = take_while(hex, |c| c.is_ascii_hexdigit())?;
+            (u32::from_str_radix(digits, 16)?, bytes_read + 2)
+        }
+        None => {
+            let (digits, bytes_read) = take_while(&data[1..], |c| c.is_ascii_digit())?;
+            (digits.parse::<u32>()?, bytes_read + 1)
+        }
+    };
+
+    Ok((TokenKind::CharCode(code), bytes_read))
+}
+
 fn skip(data: &str) -> usize {
     let mut remaining = data;
 
@@ -225,6 +419,8 @@ fn skip_comments(data: &str) -> usize {
     0
 }
 
+/// Skip past the next `pattern`, or to the end of `data` when there is none,
+/// e.g. for a `//` comment on the last line.
 fn skip_untill<'a>(mut data: &'a str, pattern: &str) -> &'a str {
     while !data.is_empty() && !data.starts_with(pattern) {
         let next_char_size = data
@@ -234,7 +430,7 @@ fn skip_untill<'a>(mut data: &'a str, pattern: &str) -> &'a str {
             .len_utf8();
         data = &data[next_char_size..];
     }
-    &data[pattern.len()..]
+    data.get(pattern.len()..).unwrap_or(data)
 }
 
 /// Consumes bytes while predicate evaluates to true.
@@ -305,7 +501,7 @@ macro_rules! skip_test {
 #[cfg(test)]
 mod tokenize_identifier_tests {
     use super::tokenize_identifier;
-    use crate::parsers::delphi::lexer::TokenKind;
+    use crate::parsers::delphi::lexer::{Keyword, TokenKind};
 
     lexer_test!(tokenize_a_single_letter, tokenize_identifier, "F" => "F");
     lexer_test!(tokenize_an_identifer, tokenize_identifier, "Foo" => "Foo");
@@ -321,6 +517,11 @@ mod tokenize_identifier_tests {
         tokenize_identifier,
         ".Foo_bar"
     );
+    lexer_test!(tokenize_keyword, tokenize_identifier, "begin" => Keyword::Begin);
+    lexer_test!(tokenize_keyword_ignores_case, tokenize_identifier, "ProCedure" => Keyword::Procedure);
+    lexer_test!(tokenize_uppercase_keyword, tokenize_identifier, "UNTIL" => Keyword::Until);
+    lexer_test!(tokenize_ident_starting_with_keyword, tokenize_identifier, "Ending" => "Ending");
+    lexer_test!(tokenize_ident_containing_keyword, tokenize_identifier, "do_it" => "do_it");
 }
 
 #[cfg(test)]
@@ -340,6 +541,67 @@ mod tokenize_number_tests {
         "asdfghj"
     );
     lexer_test!(tokenizing_decimal_stops_at_alpha, tokenize_number, "123.4asdfghj" => 123.4);
+    lexer_test!(tokenize_scientific_decimal, tokenize_number, "1.5e10" => 1.5e10);
+    lexer_test!(tokenize_scientific_integer, tokenize_number, "2E-3" => 2e-3);
+    lexer_test!(tokenize_exponent_with_plus, tokenize_number, "4e+2;" => 400.0);
+    lexer_test!(FAIL: exponent_needs_digits, tokenize_number, "1e");
+    lexer_test!(FAIL: signed_exponent_needs_digits, tokenize_number, "1.5e-");
+
+    #[test]
+    fn malformed_exponents_are_conversion_errors() {
+        use crate::parsers::error::ParseErrorKind;
+
+        let err = tokenize_number("1e+x").unwrap_err();
+        assert_eq!(ParseErrorKind::ConversionError, err.kind);
+    }
+}
+
+#[cfg(test)]
+mod tokenize_hex_tests {
+    use super::tokenize_hex;
+    use crate::parsers::delphi::lexer::TokenKind;
+
+    lexer_test!(tokenize_short_hex, tokenize_hex, "$1F" => 0x1F);
+    lexer_test!(tokenize_longer_hex, tokenize_hex, "$FF00" => 0xFF00);
+    lexer_test!(tokenize_lowercase_hex, tokenize_hex, "$ab;" => 0xAB);
+    lexer_test!(FAIL: hex_requires_digits, tokenize_hex, "$");
+    lexer_test!(FAIL: hex_requires_hex_digits, tokenize_hex, "$g");
+}
+
+#[cfg(test)]
+mod tokenize_char_code_tests {
+    use super::tokenize_char_code;
+    use crate::parsers::delphi::lexer::TokenKind;
+
+    lexer_test!(tokenize_decimal_char_code, tokenize_char_code, "#13" => TokenKind::CharCode(13));
+    lexer_test!(tokenize_hex_char_code, tokenize_char_code, "#$0D" => TokenKind::CharCode(13));
+    lexer_test!(tokenize_char_code_stops_at_quote, tokenize_char_code, "#65'a'" => TokenKind::CharCode(65));
+    lexer_test!(FAIL: char_code_requires_digits, tokenize_char_code, "#a");
+    lexer_test!(FAIL: hex_char_code_requires_digits, tokenize_char_code, "#$");
+}
+
+#[cfg(test)]
+mod tokenize_string_tests {
+    use super::tokenize_string;
+    use crate::parsers::delphi::lexer::TokenKind;
+
+    fn quoted(s: &str) -> TokenKind {
+        TokenKind::QuotedString(s.to_string())
+    }
+
+    lexer_test!(tokenize_basic_string, tokenize_string, "'hello'" => quoted("hello"));
+    lexer_test!(tokenize_empty_string, tokenize_string, "''" => quoted(""));
+    lexer_test!(tokenize_string_with_doubled_quote, tokenize_string, "'it''s'" => quoted("it's"));
+    lexer_test!(tokenize_string_of_a_quote, tokenize_string, "''''" => quoted("'"));
+    lexer_test!(tokenize_string_stops_at_closing_quote, tokenize_string, "'a' + 'b'" => quoted("a"));
+    lexer_test!(FAIL: unterminated_string, tokenize_string, "'hello");
+    lexer_test!(FAIL: string_ending_in_doubled_quote, tokenize_string, "'it''");
+
+    #[test]
+    fn doubled_quotes_are_part_of_the_token() {
+        let (_, bytes_read) = tokenize_string("'it''s' rest").unwrap();
+        assert_eq!(7, bytes_read);
+    }
 }
 
 #[cfg(test)]
@@ -374,6 +636,8 @@ mod comment_tests {
     comment_test!(comment_skip_round_brackets, "(* Hello World *) asd" => 17);
     comment_test!(comment_skip_ignores_alphanumeric, "123 hello world" => 0);
     comment_test!(comment_skip_ignores_whitespace, "   (* *) 123 hello world" => 0);
+    comment_test!(comment_skip_unterminated_curly_braces, "{ unterminated" => 14);
+    comment_test!(comment_skip_slash_slash_at_eof, "// last line" => 12);
 }
 
 #[cfg(test)]
@@ -385,6 +649,7 @@ mod skip_tests {
     skip_test!(comment_skip_round_brackets, "(* Hello World *) asd" => 18);
     skip_test!(comment_skip_ignores_alphanumeric, "123 hello world" => 0);
     skip_test!(comment_skip_ignores_whitespace, "   (* *) 123 hello world" => 9);
+    skip_test!(skip_to_end_of_unterminated_comment, " { unterminated" => 15);
 }
 
 #[cfg(test)]
@@ -394,6 +659,9 @@ mod tokenize_next_token_tests {
 
     lexer_test!(central_tokenizer_integer, tokenize_next_token, "1234" => 1234);
     lexer_test!(central_tokenizer_decimal, tokenize_next_token, "123.4" => 123.4);
+    lexer_test!(central_tokenizer_nine, tokenize_next_token, "9" => 9);
+    lexer_test!(central_tokenizer_leading_nine, tokenize_next_token, "99" => 99);
+    lexer_test!(central_tokenizer_decimal_leading_nine, tokenize_next_token, "9.5" => 9.5);
     lexer_test!(central_tokenizer_dot, tokenize_next_token, "." => TokenKind::Dot);
     lexer_test!(central_tokenizer_plus, tokenize_next_token, "+" => TokenKind::Plus);
     lexer_test!(central_tokenizer_minus, tokenize_next_token, "-" => TokenKind::Minus);
@@ -408,29 +676,58 @@ mod tokenize_next_token_tests {
     lexer_test!(central_tokenizer_close_square, tokenize_next_token, "]" => TokenKind::CloseSquare);
     lexer_test!(central_tokenizer_colon, tokenize_next_token, ":" => TokenKind::Colon);
     lexer_test!(central_tokenizer_semi_colon, tokenize_next_token, ";" => TokenKind::Semicolon);
+    lexer_test!(central_tokenizer_comma, tokenize_next_token, "," => TokenKind::Comma);
+    lexer_test!(central_tokenizer_less_than, tokenize_next_token, "<" => TokenKind::LessThan);
+    lexer_test!(central_tokenizer_greater_than, tokenize_next_token, ">" => TokenKind::GreaterThan);
 }
 
 #[cfg(test)]
 mod tokenizer_tests {
     use super::tokenize;
-    use crate::parsers::delphi::lexer::TokenKind;
+    use crate::parsers::delphi::lexer::{Span, TokenKind};
     use crate::parsers::error::ParseErrorKind;
 
     #[test]
     fn tokenize_a_basic_expression() {
         let src = "foo = 1 + 2.34";
         let should_be = vec![
-            (TokenKind::from("foo"), 0, 3),
-            (TokenKind::Equals, 4, 5),
-            (TokenKind::from(1), 6, 7),
-            (TokenKind::Plus, 8, 9),
-            (TokenKind::from(2.34), 10, 14),
+            (TokenKind::from("foo"), Span::new(0, 3, 1, 1)),
+            (TokenKind::Equals, Span::new(4, 5, 1, 5)),
+            (TokenKind::from(1), Span::new(6, 7, 1, 7)),
+            (TokenKind::Plus, Span::new(8, 9, 1, 9)),
+            (TokenKind::from(2.34), Span::new(10, 14, 1, 11)),
+        ];
+
+        let got = tokenize(src).unwrap();
+        assert_eq!(got, should_be);
+    }
+
+    #[test]
+    fn tokenize_strings_joined_by_char_codes() {
+        let src = "'a'#13'b'";
+        let should_be = vec![
+            (
+                TokenKind::QuotedString("a".to_string()),
+                Span::new(0, 3, 1, 1),
+            ),
+            (TokenKind::CharCode(13), Span::new(3, 6, 1, 4)),
+            (
+                TokenKind::QuotedString("b".to_string()),
+                Span::new(6, 9, 1, 7),
+            ),
         ];
 
         let got = tokenize(src).unwrap();
         assert_eq!(got, should_be);
     }
 
+    #[test]
+    fn lone_dollar_is_an_unknown_character() {
+        let err = tokenize("x := $;").unwrap_err();
+        assert_eq!(ParseErrorKind::UnknownCharacter('$'), err.kind);
+        assert_eq!(Some(5), err.index);
+    }
+
     #[test]
     fn tokenizer_detects_invalid_stuff() {
         let src = "foo bar `%^&\\";
@@ -439,5 +736,34 @@ mod tokenizer_tests {
         let err = tokenize(src).unwrap_err();
         assert_eq!(ParseErrorKind::UnknownCharacter('`'), err.kind);
         assert_eq!(Some(index_of_backtick), err.index);
+        assert_eq!(Some((1, 9)), err.position);
+    }
+
+    #[test]
+    fn spans_track_lines_and_columns() {
+        let src = "a\nbb (* x\r\n *) c\r\nd";
+        let spans: Vec<(usize, usize)> = tokenize(src)
+            .unwrap()
+            .into_iter()
+            .map(|(_, span)| (span.line, span.column))
+            .collect();
+
+        assert_eq!(vec![(1, 1), (2, 1), (3, 5), (4, 1)], spans);
+    }
+
+    #[test]
+    fn columns_count_characters_not_bytes() {
+        let src = "'héllo' ü";
+        let (_, span) = tokenize(src).unwrap().pop().unwrap();
+
+        assert_eq!(Span::new(9, 11, 1, 9), span);
+    }
+
+    #[test]
+    fn errors_report_line_and_column() {
+        let err = tokenize("x\r\n  é`").unwrap_err();
+        assert_eq!(ParseErrorKind::UnknownCharacter('`'), err.kind);
+        assert_eq!(Some(7), err.index);
+        assert_eq!(Some((2, 4)), err.position);
     }
 }
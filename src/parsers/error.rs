@@ -1,5 +1,6 @@
 use std::error::Error;
 use std::fmt;
+use std::io;
 use std::num::{ParseFloatError, ParseIntError};
 
 #[derive(Debug, PartialEq)]
@@ -9,6 +10,12 @@ pub enum ParseErrorKind {
     NoMatches,
     ConversionError,
     UnknownCharacter(char),
+    /// The source couldn't be read.
+    Io,
+    /// The source isn't valid Rust.
+    Syntax,
+    /// The language of a file can't be told from its extension.
+    UnknownLanguage,
 }
 
 impl fmt::Display for ParseErrorKind {
@@ -22,7 +29,9 @@ pub struct ParseError {
     pub kind: ParseErrorKind,
     pub msg: Option<String>,
     pub index: Option<usize>,
-    pub source: Option<Box<dyn Error>>,
+    /// 1-based line and column the error was found at, when known.
+    pub position: Option<(usize, usize)>,
+    pub source: Option<Box<dyn Error + Send + Sync>>,
 }
 
 impl ParseError {
@@ -31,6 +40,7 @@ impl ParseError {
             kind,
             msg: None,
             index: None,
+            position: None,
             source: None,
         }
     }
@@ -42,7 +52,7 @@ impl ParseError {
         }
     }
 
-    pub fn source(self, source: Box<dyn Error>) -> ParseError {
+    pub fn with_source(self, source: Box<dyn Error + Send + Sync>) -> ParseError {
         ParseError {
             source: Some(source),
             ..self
@@ -55,6 +65,13 @@ impl ParseError {
             ..self
         }
     }
+
+    pub fn position(self, line: usize, column: usize) -> ParseError {
+        ParseError {
+            position: Some((line, column)),
+            ..self
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -67,13 +84,21 @@ impl fmt::Display for ParseError {
     }
 }
 
-impl Error for ParseError {}
+/// The underlying error, e.g. the `io::Error` of a file that couldn't be
+/// read, for callers that need more than the kind.
+impl Error for ParseError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.source
+            .as_ref()
+            .map(|source| source.as_ref() as &(dyn Error + 'static))
+    }
+}
 
 impl From<ParseFloatError> for ParseError {
     fn from(other: ParseFloatError) -> ParseError {
         ParseError::kind(ParseErrorKind::ConversionError)
             .msg("Cannot convert token to float".to_string())
-            .source(Box::new(other))
+            .with_source(Box::new(other))
     }
 }
 
@@ -81,8 +106,58 @@ impl From<ParseIntError> for ParseError {
     fn from(other: ParseIntError) -> ParseError {
         ParseError::kind(ParseErrorKind::ConversionError)
             .msg("Cannot convert token to int".to_string())
-            .source(Box::new(other))
+            .with_source(Box::new(other))
+    }
+}
+
+impl From<io::Error> for ParseError {
+    fn from(other: io::Error) -> ParseError {
+        ParseError::kind(ParseErrorKind::Io)
+            .msg(other.to_string())
+            .with_source(Box::new(other))
+    }
+}
+
+impl From<syn::Error> for ParseError {
+    fn from(other: syn::Error) -> ParseError {
+        let start = other.span().start();
+        let error = ParseError::kind(ParseErrorKind::Syntax).msg(other.to_string());
+
+        // errors at the end of the input have no real span, only line 0
+        let error = if start.line > 0 {
+            error.position(start.line, start.column + 1)
+        } else {
+            error
+        };
+        error.with_source(Box::new(other))
     }
 }
 
 pub type ParseResult<T> = Result<T, ParseError>;
+
+#[cfg(test)]
+mod tests {
+    use super::{ParseError, ParseErrorKind};
+    use std::error::Error;
+    use std::io;
+
+    #[test]
+    fn syntax_errors_keep_their_position() {
+        let err: ParseError = syn::parse_file("fn f() {}\nfn g( {}").unwrap_err().into();
+
+        assert_eq!(ParseErrorKind::Syntax, err.kind);
+        assert_eq!(Some((2, 5)), err.position);
+    }
+
+    #[test]
+    fn io_errors_are_their_source() {
+        let err: ParseError = io::Error::new(io::ErrorKind::NotFound, "gone").into();
+
+        assert_eq!(ParseErrorKind::Io, err.kind);
+        let source = err
+            .source()
+            .and_then(|source| source.downcast_ref::<io::Error>())
+            .unwrap();
+        assert_eq!(io::ErrorKind::NotFound, source.kind());
+    }
+}
@@ -1,164 +1,1086 @@
-use crate::parsers::error::{ParseError, ParseErrorKind};
-use std::error::Error;
+use crate::analyzer::{AnalyzeOptions, Analyzer};
+use crate::cache::{self, FunctionCache};
+use crate::discovery::{self, ScanOptions};
+use crate::parsers::error::ParseResult;
+use proc_macro2::LineColumn;
+use quote::ToTokens;
+use std::collections::HashMap;
 use std::fmt;
 use std::fs::File;
 use std::io::Read;
+use std::str::FromStr;
 use syn;
+use syn::parse::{ParseStream, Parser};
+use syn::punctuated::Punctuated;
 
-type ParseResult<T> = Result<T, Box<dyn Error + 'static>>;
-
-fn get_ast(file_path: String) -> ParseResult<syn::File> {
+fn read_source(file_path: &str) -> ParseResult<String> {
     let mut src: String = String::new();
-    let mut file: File = File::open(&file_path)?;
+    let mut file: File = File::open(file_path)?;
     file.read_to_string(&mut src)?;
 
-    Ok(syn::parse_file(&src)?)
+    Ok(src)
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ComplexityNodeKind {
     Fn,
     Method,
     Impl,
+    Trait,
+    Mod,
     File,
 }
 
+impl ComplexityNodeKind {
+    /// Whether nodes of this kind are measured themselves, rather than
+    /// grouping the nodes nested in them.
+    pub fn is_function(&self) -> bool {
+        matches!(self, ComplexityNodeKind::Fn | ComplexityNodeKind::Method)
+    }
+}
+
 impl fmt::Display for ComplexityNodeKind {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(f, "{:?}", self)
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComplexityNode {
     pub name: String,
     pub kind: ComplexityNodeKind,
     pub complexity: usize,
+    /// Cognitive complexity of a function, see `Metric::Cognitive`. Reports
+    /// written before it existed read back as 0.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub cognitive: usize,
+    /// Whether analysis stopped early because expressions were nested deeper
+    /// than `AnalyzeOptions::max_depth`, making `complexity` a lower bound.
+    pub truncated: bool,
+    /// 1-based line the item starts on, 0 when unknown (e.g. for files).
+    pub line: usize,
+    /// 1-based column of the item's name, 0 when unknown.
+    pub column: usize,
+    /// Lines a function spans, from its name through the end of its body,
+    /// 0 when unknown. Reports written before it existed read back as 0.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub loc: usize,
+    /// The most deeply nested branching construct of a function. Not read
+    /// back from JSON, as constructs are only ever static strings.
+    #[cfg_attr(feature = "serde", serde(skip_deserializing))]
+    pub deepest: Option<Nesting>,
+    /// Whether a function is part of the public API: declared `pub`, or a
+    /// method of a trait impl.
+    pub public: bool,
+    /// Left out of `nodes`, `functions` and so every report, but still
+    /// counted in the totals of its parents, see `hide_private`.
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub hidden: bool,
     pub children: Vec<ComplexityNode>,
 }
 
+/// A branching construct (`if`, `match`, ...) and how many other branching
+/// constructs it is nested in, counting itself.
+#[derive(Debug, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Nesting {
+    pub construct: &'static str,
+    pub line: usize,
+    pub level: usize,
+}
+
 impl ComplexityNode {
-    fn new(name: String, kind: ComplexityNodeKind) -> ComplexityNode {
+    pub fn new(name: String, kind: ComplexityNodeKind) -> ComplexityNode {
         ComplexityNode {
             name,
             kind,
             complexity: 0,
+            cognitive: 0,
+            truncated: false,
+            line: 0,
+            column: 0,
+            loc: 0,
+            deepest: None,
+            public: false,
+            hidden: false,
             children: vec![],
         }
     }
 
-    fn with_complexity(mut self, complexity: usize) -> ComplexityNode {
+    pub fn with_complexity(mut self, complexity: usize) -> ComplexityNode {
         self.complexity = complexity;
         self
     }
 
-    fn add_child(&mut self, child: ComplexityNode) {
+    pub fn with_cognitive(mut self, cognitive: usize) -> ComplexityNode {
+        self.cognitive = cognitive;
+        self
+    }
+
+    pub fn with_truncated(mut self, truncated: bool) -> ComplexityNode {
+        self.truncated = truncated;
+        self
+    }
+
+    pub fn with_position(mut self, line: usize, column: usize) -> ComplexityNode {
+        self.line = line;
+        self.column = column;
+        self
+    }
+
+    pub fn with_loc(mut self, loc: usize) -> ComplexityNode {
+        self.loc = loc;
+        self
+    }
+
+    pub fn with_public(mut self, public: bool) -> ComplexityNode {
+        self.public = public;
+        self
+    }
+
+    pub fn add_child(&mut self, child: ComplexityNode) {
         self.children.push(child);
     }
+
+    /// Complexity per line, so a function of complexity 8 in 8 lines is
+    /// denser than one in 200. Functions of unknown length count as one line.
+    pub fn density(&self) -> f64 {
+        self.complexity as f64 / self.loc.max(1) as f64
+    }
+
+    /// Complexity of this node and everything nested in it, e.g. of all the
+    /// methods of an impl.
+    pub fn total(&self) -> usize {
+        self.children.iter().fold(self.complexity, |total, child| {
+            total.saturating_add(child.total())
+        })
+    }
+}
+
+/// Tunable costs for constructs whose scoring differs between teams.
+///
+/// The default leaves every weight at 0, which matches the plain
+/// cyclomatic count.
+#[derive(Debug, Default, Clone)]
+pub struct Weights {
+    /// Extra complexity added by a trailing `else` block, on top of whatever
+    /// its contents add. `else if` is unaffected, as the nested `if` already
+    /// counts as its own decision.
+    pub if_else: usize,
+}
+
+/// Which constructs count as decisions, for teams whose definition of
+/// cyclomatic complexity leaves some out. Everything counts by default.
+#[derive(Debug, Clone)]
+pub struct ComplexityConfig {
+    /// `&&` and `||`, which short-circuit.
+    pub count_boolean_operators: bool,
+    /// Every `match` arm beyond the first, and every `matches!` alternative.
+    /// Without it a `match` or `matches!` is a single decision, like an `if`.
+    /// Guards count either way.
+    pub count_match_arms: bool,
+    /// `?`, an early return.
+    pub count_try: bool,
+}
+
+impl Default for ComplexityConfig {
+    fn default() -> ComplexityConfig {
+        ComplexityConfig {
+            count_boolean_operators: true,
+            count_match_arms: true,
+            count_try: true,
+        }
+    }
+}
+
+/// The number reported and checked as a function's complexity.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Metric {
+    /// The count of decisions, `ComplexityNode::complexity`.
+    Cyclomatic,
+    /// How hard a function is to follow, where nesting costs extra:
+    ///
+    /// - `if`, `match`, `for`, `while` and `loop` cost 1, plus 1 for every
+    ///   one of them they are nested in. A `match` costs the same however
+    ///   many arms it has.
+    /// - `else if` and `else` cost 1, as they are part of the `if` rather
+    ///   than nested in it.
+    /// - a run of the same boolean operator, like `a && b && c`, costs 1;
+    ///   every switch between `&&` and `||` costs 1 more.
+    ///
+    /// `?`, `break` and `continue` are free. Delphi routines have no
+    /// cognitive complexity yet and are always 0.
+    Cognitive,
 }
 
-#[derive(Debug)]
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Metric, String> {
+        match s {
+            "cyclomatic" => Ok(Metric::Cyclomatic),
+            "cognitive" => Ok(Metric::Cognitive),
+            other => Err(format!("unknown metric: {}", other)),
+        }
+    }
+}
+
+/// The analysis of one file.
+///
+/// Trees are built on worker threads during parallel scans, so this (and
+/// `ComplexityNode`) must stay `Send + Sync`: avoid `Rc`, `RefCell` or
+/// non-`Sync` trait objects in their fields.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct ComplexityTree {
     pub root: ComplexityNode,
 }
 
 impl ComplexityTree {
     pub fn generate(file_path: String) -> ParseResult<ComplexityTree> {
-        let file: syn::File = get_ast(file_path.clone())?;
+        let src = read_source(&file_path)?;
+
+        ComplexityTree::from_source(file_path, &src)
+    }
+
+    pub fn generate_with_options(
+        file_path: String,
+        opts: &AnalyzeOptions,
+    ) -> ParseResult<ComplexityTree> {
+        let src = read_source(&file_path)?;
+
+        ComplexityTree::from_source_with_options(file_path, &src, opts)
+    }
+
+    /// Analyze source that is already in memory with the default options;
+    /// `name` becomes the name of the root node.
+    pub fn from_source(name: String, src: &str) -> ParseResult<ComplexityTree> {
+        ComplexityTree::from_source_with_options(name, src, &AnalyzeOptions::default())
+    }
+
+    /// Analyze source that is already in memory; `name` becomes the name of
+    /// the root node.
+    pub fn from_source_with_options(
+        name: String,
+        src: &str,
+        opts: &AnalyzeOptions,
+    ) -> ParseResult<ComplexityTree> {
+        build(name, src, opts, None)
+    }
+
+    /// Like `from_source_with_options`, but functions whose tokens are already
+    /// in `cache` aren't walked again. The cache must only ever be used with
+    /// the same `opts`.
+    pub fn from_source_with_cache(
+        name: String,
+        src: &str,
+        opts: &AnalyzeOptions,
+        cache: &mut FunctionCache,
+    ) -> ParseResult<ComplexityTree> {
+        build(name, src, opts, Some(cache))
+    }
+
+    /// Analyze every file matched by `patterns`, capturing each file's result
+    /// independently so one bad file doesn't stop the rest of the scan.
+    ///
+    /// Results are sorted by path.
+    pub fn generate_all(
+        patterns: &[String],
+        opts: &ScanOptions,
+    ) -> Vec<(String, ParseResult<ComplexityTree>)> {
+        let analyzer = Analyzer::new(opts.analyze.clone());
+
+        discovery::scan(patterns, opts)
+            .into_iter()
+            .map(|file| {
+                let tree = analyzer.analyze(&file);
+                (file, tree)
+            })
+            .collect()
+    }
+
+    /// Hide every function and method that isn't part of the public API.
+    /// They still count towards the totals of their impls and file.
+    pub fn hide_private(&mut self) {
+        hide_private(&mut self.root);
+    }
+
+    /// Make `metric` the complexity of every function, so every report and
+    /// check uses it.
+    pub fn use_metric(&mut self, metric: Metric) {
+        if metric == Metric::Cognitive {
+            use_cognitive(&mut self.root);
+        }
+    }
+
+    /// Every node of the tree but the file itself along with its
+    /// `Kind: name > Kind: name` breadcrumb, parents before their children.
+    pub fn nodes(&self) -> impl Iterator<Item = (String, &ComplexityNode)> {
+        let mut nodes = vec![];
+        for child in self.root.children.iter() {
+            collect_nodes(child, String::new(), &mut nodes);
+        }
 
-        let mut root = ComplexityNode::new(file_path, ComplexityNodeKind::File);
-        process_file(file, &mut root);
+        nodes.into_iter()
+    }
 
-        Ok(ComplexityTree { root })
+    /// Every function and method of the tree along with its breadcrumb, in
+    /// source order.
+    pub fn functions(&self) -> impl Iterator<Item = (String, &ComplexityNode)> {
+        self.nodes().filter(|(_, node)| node.kind.is_function())
     }
 }
 
-/// parse ast to get complexity from valid blocks
-// TODO: add macros complexity later i.e. Macro, Macro2
-fn process_file(ast: syn::File, parent: &mut ComplexityNode) {
-    for item in ast.items {
+fn use_cognitive(node: &mut ComplexityNode) {
+    if node.kind.is_function() {
+        node.complexity = node.cognitive;
+    }
+    for child in node.children.iter_mut() {
+        use_cognitive(child);
+    }
+}
+
+fn hide_private(node: &mut ComplexityNode) {
+    for child in node.children.iter_mut() {
+        if child.kind.is_function() && !child.public {
+            child.hidden = true;
+        }
+        hide_private(child);
+    }
+}
+
+fn collect_nodes<'a>(
+    node: &'a ComplexityNode,
+    path: String,
+    nodes: &mut Vec<(String, &'a ComplexityNode)>,
+) {
+    if node.hidden {
+        return;
+    }
+    let mut path_here: String = path;
+    if !path_here.is_empty() {
+        path_here += " > ";
+    }
+    path_here += &format!("{}: {}", node.kind, node.name);
+
+    nodes.push((path_here.clone(), node));
+    for child in node.children.iter() {
+        collect_nodes(child, path_here.clone(), nodes);
+    }
+}
+
+/// Parse `src` into the tree of a file named `name`, taking the nodes of
+/// unchanged functions from `cache` when there is one.
+// TODO: functions defined inside item macros (`macro_rules!` bodies and
+// the like) aren't measured, their tokens are never parsed as items
+fn build(
+    name: String,
+    src: &str,
+    opts: &AnalyzeOptions,
+    cache: Option<&mut FunctionCache>,
+) -> ParseResult<ComplexityTree> {
+    let file: syn::File = syn::parse_file(src)?;
+
+    let mut root = ComplexityNode::new(name, ComplexityNodeKind::File);
+    match cache {
+        Some(cache) => {
+            cache.begin(&root.name);
+            process_file(file, &mut root, opts, Some(&mut *cache));
+            cache.finish();
+        }
+        None => process_file(file, &mut root, opts, None),
+    }
+
+    Ok(ComplexityTree { root })
+}
+
+fn process_file(
+    ast: syn::File,
+    parent: &mut ComplexityNode,
+    opts: &AnalyzeOptions,
+    cache: Option<&mut FunctionCache>,
+) {
+    // `#![cfg(test)]` on the file gates the whole of it, e.g. a `tests.rs`
+    // declared from a `#[cfg(test)] mod tests;`
+    if !opts.include_tests && is_test_only(&ast.attrs) {
+        return;
+    }
+
+    let mut defaults = TraitDefaults::new();
+    if opts.resolve_trait_defaults {
+        trait_defaults(&ast.items, &mut Vec::new(), &mut defaults, opts);
+    }
+    let mut scope = Scope {
+        module: Vec::new(),
+        defaults: &defaults,
+    };
+    process_items(ast.items, parent, &mut scope, opts, cache);
+}
+
+/// Where in the file items are, to resolve the traits impls name.
+struct Scope<'a> {
+    /// The names of the inline modules the items are in, outermost first.
+    module: Vec<String>,
+    defaults: &'a TraitDefaults,
+}
+
+fn process_items(
+    items: Vec<syn::Item>,
+    parent: &mut ComplexityNode,
+    scope: &mut Scope<'_>,
+    opts: &AnalyzeOptions,
+    mut cache: Option<&mut FunctionCache>,
+) {
+    for item in items {
         match item {
-            syn::Item::Fn(ast) => process_item_fn(ast, parent),
-            syn::Item::Impl(ast) => process_item_impl(ast, parent),
-            syn::Item::Mod(_) => {}
-            syn::Item::Trait(_) => {}
+            syn::Item::Fn(ast) if is_skipped_test(&ast.attrs, opts) => {}
+            syn::Item::Impl(ast) if is_skipped_test(&ast.attrs, opts) => {}
+            syn::Item::Mod(ast) if is_skipped_test(&ast.attrs, opts) => {}
+            syn::Item::Trait(ast) if is_skipped_test(&ast.attrs, opts) => {}
+            syn::Item::Fn(ast) => process_item_fn(ast, parent, opts, cache.as_deref_mut()),
+            syn::Item::Impl(ast) => {
+                process_item_impl(ast, parent, scope, opts, cache.as_deref_mut())
+            }
+            syn::Item::Mod(ast) => process_item_mod(ast, parent, scope, opts, cache.as_deref_mut()),
+            syn::Item::Trait(ast) => process_item_trait(ast, parent, opts, cache.as_deref_mut()),
             _ => {}
         }
     }
 }
 
-fn process_item_fn(ast: syn::ItemFn, parent: &mut ComplexityNode) {
-    let node = ComplexityNode::new(ast.sig.ident.to_string(), ComplexityNodeKind::Fn)
-        .with_complexity((*ast.block).process());
+/// `mod foo;` declarations live in files of their own, which are analyzed
+/// when they are passed in, so only inline modules are walked.
+fn process_item_mod(
+    ast: syn::ItemMod,
+    parent: &mut ComplexityNode,
+    scope: &mut Scope<'_>,
+    opts: &AnalyzeOptions,
+    cache: Option<&mut FunctionCache>,
+) {
+    let (_, items) = match ast.content {
+        Some(content) => content,
+        None => return,
+    };
+
+    let mut node = ComplexityNode::new(ast.ident.to_string(), ComplexityNodeKind::Mod);
+    scope.module.push(ast.ident.to_string());
+    process_items(items, &mut node, scope, opts, cache);
+    scope.module.pop();
 
     parent.add_child(node);
 }
 
-fn process_item_impl(ast: syn::ItemImpl, parent: &mut ComplexityNode) {
-    let mut node = ComplexityNode::new(
-        get_impl_resolved_name(&ast).ok().unwrap().to_string(),
-        ComplexityNodeKind::Impl,
-    );
+fn process_item_fn(
+    ast: syn::ItemFn,
+    parent: &mut ComplexityNode,
+    opts: &AnalyzeOptions,
+    cache: Option<&mut FunctionCache>,
+) {
+    if is_ignored(&ast.attrs) {
+        return;
+    }
+
+    let start = ast.sig.ident.span().start();
+    let public = is_public(&ast.vis);
+
+    let node = cached(cache, ComplexityNodeKind::Fn, start, ast, |ast| {
+        item_fn_node(ast, Context::new(opts))
+    });
+    parent.add_child(node.with_public(public));
+}
+
+/// Walk a function, top level or nested in another one, with a fresh `ctx`.
+fn item_fn_node(ast: syn::ItemFn, mut ctx: Context) -> ComplexityNode {
+    let start = ast.sig.ident.span().start();
+    let name = ast.sig.ident.to_string();
+    let loc = lines_of(start, &ast.block);
+    let complexity = ast
+        .sig
+        .process(&mut ctx)
+        .saturating_add((*ast.block).process(&mut ctx));
+
+    ctx.finish(name, ComplexityNodeKind::Fn, start, complexity)
+        .with_loc(loc)
+}
+
+/// Lines from the one `start` is on through the closing brace of `block`.
+fn lines_of(start: LineColumn, block: &syn::Block) -> usize {
+    (block.brace_token.span.end().line + 1).saturating_sub(start.line)
+}
+
+/// Reuse the node of a function with the same tokens from `cache`, or build
+/// it with `walk` and remember it.
+fn cached<T, F>(
+    cache: Option<&mut FunctionCache>,
+    kind: ComplexityNodeKind,
+    start: LineColumn,
+    ast: T,
+    walk: F,
+) -> ComplexityNode
+where
+    T: ToTokens,
+    F: FnOnce(T) -> ComplexityNode,
+{
+    let cache = match cache {
+        Some(cache) => cache,
+        None => return walk(ast),
+    };
+
+    let fingerprint = cache::fingerprint(&ast.to_token_stream());
+    if let Some(node) = cache.get(&kind, fingerprint, start) {
+        return node;
+    }
+
+    let node = walk(ast);
+    cache.insert(kind, fingerprint, &node);
+    node
+}
+
+/// Nodes of the default methods of the traits in a file, by the path of the
+/// trait from the root of the file, e.g. `["outer", "Greeter"]`.
+type TraitDefaults = HashMap<Vec<String>, Vec<ComplexityNode>>;
+
+/// Collect the defaults of the traits declared in `items` and the inline
+/// modules in them, `module` being the path of `items`.
+///
+/// Only traits declared in the same file are seen, so impls of traits from
+/// other files or crates never get their defaults attributed.
+fn trait_defaults(
+    items: &[syn::Item],
+    module: &mut Vec<String>,
+    defaults: &mut TraitDefaults,
+    opts: &AnalyzeOptions,
+) {
+    for item in items {
+        if let syn::Item::Mod(ast) = item {
+            if let Some((_, items)) = ast.content.as_ref() {
+                module.push(ast.ident.to_string());
+                trait_defaults(items, module, defaults, opts);
+                module.pop();
+            }
+        }
+        if let syn::Item::Trait(ast) = item {
+            // default methods are as public as the trait
+            let public = is_public(&ast.vis);
+            let methods = ast
+                .items
+                .iter()
+                .filter_map(|item| match item {
+                    syn::TraitItem::Method(method)
+                        if method.default.is_some() && !is_ignored(&method.attrs) =>
+                    {
+                        Some(
+                            trait_item_method_node(method.clone(), Context::new(opts))
+                                .with_public(public),
+                        )
+                    }
+                    _ => None,
+                })
+                .collect();
+
+            let mut path = module.clone();
+            path.push(ast.ident.to_string());
+            defaults.insert(path, methods);
+        }
+    }
+}
 
+/// The path from the root of the file of the trait `path` names in
+/// `module`, following `crate::`, `self::` and `super::`. `use` imports
+/// aren't followed, so a path through one names no trait of the file.
+fn trait_path(path: &syn::Path, module: &[String]) -> Option<Vec<String>> {
+    // `::name` is a path into another crate
+    if path.leading_colon.is_some() {
+        return None;
+    }
+
+    let mut resolved = module.to_vec();
+    let mut leading = true;
+    for (i, segment) in path.segments.iter().enumerate() {
+        let ident = segment.ident.to_string();
+        match ident.as_str() {
+            "crate" if i == 0 => resolved.clear(),
+            "self" if i == 0 => {}
+            "super" if leading => {
+                resolved.pop()?;
+            }
+            _ => {
+                leading = false;
+                resolved.push(ident);
+            }
+        }
+    }
+    Some(resolved)
+}
+
+/// Methods declared without a default body have nothing to measure and are
+/// left out.
+fn process_item_trait(
+    ast: syn::ItemTrait,
+    parent: &mut ComplexityNode,
+    opts: &AnalyzeOptions,
+    mut cache: Option<&mut FunctionCache>,
+) {
+    let mut node = ComplexityNode::new(ast.ident.to_string(), ComplexityNodeKind::Trait);
+
+    // default methods are as public as the trait
+    let public = is_public(&ast.vis);
     for item in ast.items {
-        match item {
-            syn::ImplItem::Method(ast) => process_impl_item_method(ast, &mut node),
-            _ => {}
+        if let syn::TraitItem::Method(ast) = item {
+            if ast.default.is_some() && !is_ignored(&ast.attrs) {
+                let start = ast.sig.ident.span().start();
+                let method = cached(
+                    cache.as_deref_mut(),
+                    ComplexityNodeKind::Method,
+                    start,
+                    ast,
+                    |ast| trait_item_method_node(ast, Context::new(opts)),
+                );
+                node.add_child(method.with_public(public));
+            }
         }
     }
 
     parent.add_child(node);
 }
 
-fn process_impl_item_method(ast: syn::ImplItemMethod, parent: &mut ComplexityNode) {
-    let node = ComplexityNode::new(ast.sig.ident.to_string(), ComplexityNodeKind::Method)
-        .with_complexity(ast.block.process());
+/// Walk a trait method, counting its default body if it has one.
+fn trait_item_method_node(ast: syn::TraitItemMethod, mut ctx: Context) -> ComplexityNode {
+    let start = ast.sig.ident.span().start();
+    let name = ast.sig.ident.to_string();
+    let loc = ast
+        .default
+        .as_ref()
+        .map_or(0, |block| lines_of(start, block));
+    let complexity = ast
+        .sig
+        .process(&mut ctx)
+        .saturating_add(ast.default.map_or(0, |block| block.process(&mut ctx)));
+
+    ctx.finish(name, ComplexityNodeKind::Method, start, complexity)
+        .with_loc(loc)
+}
+
+fn process_item_impl(
+    ast: syn::ItemImpl,
+    parent: &mut ComplexityNode,
+    scope: &Scope<'_>,
+    opts: &AnalyzeOptions,
+    mut cache: Option<&mut FunctionCache>,
+) {
+    // derive expansions (e.g. `cargo expand` output) aren't code anyone wrote
+    if !opts.include_derived && has_attr(&ast.attrs, "automatically_derived") {
+        return;
+    }
+
+    let mut node = ComplexityNode::new(impl_label(&ast), ComplexityNodeKind::Impl);
+
+    let trait_impl = ast.trait_.is_some();
+    let trait_path = ast
+        .trait_
+        .as_ref()
+        .and_then(|(_, path, _)| trait_path(path, &scope.module));
+    for item in ast.items {
+        if let syn::ImplItem::Method(ast) = item {
+            if is_skipped_test(&ast.attrs, opts) {
+                continue;
+            }
+            process_impl_item_method(ast, &mut node, trait_impl, opts, cache.as_deref_mut());
+        }
+    }
+
+    if let Some(methods) = trait_path.and_then(|path| scope.defaults.get(&path)) {
+        for method in methods {
+            if !node.children.iter().any(|child| child.name == method.name) {
+                node.add_child(method.clone());
+            }
+        }
+    }
 
     parent.add_child(node);
 }
 
-fn get_impl_resolved_name(ast: &syn::ItemImpl) -> ParseResult<syn::Ident> {
+fn process_impl_item_method(
+    ast: syn::ImplItemMethod,
+    parent: &mut ComplexityNode,
+    trait_impl: bool,
+    opts: &AnalyzeOptions,
+    cache: Option<&mut FunctionCache>,
+) {
+    if is_ignored(&ast.attrs) {
+        return;
+    }
+
+    let start = ast.sig.ident.span().start();
+    // trait methods are as public as the trait, whatever their own `vis` says
+    let public = trait_impl || is_public(&ast.vis);
+
+    let node = cached(cache, ComplexityNodeKind::Method, start, ast, |ast| {
+        let mut ctx = Context::new(opts);
+        let name = ast.sig.ident.to_string();
+        let loc = lines_of(start, &ast.block);
+        let complexity = ast
+            .sig
+            .process(&mut ctx)
+            .saturating_add(ast.block.process(&mut ctx));
+
+        ctx.finish(name, ComplexityNodeKind::Method, start, complexity)
+            .with_loc(loc)
+    });
+    parent.add_child(node.with_public(public));
+}
+
+fn is_public(vis: &syn::Visibility) -> bool {
+    matches!(vis, syn::Visibility::Public(_))
+}
+
+/// Whether `attrs` opt a function out of the report, with
+/// `#[complexity(ignore)]` or by allowing clippy's complexity lint
+/// (`cognitive_complexity`, formerly `cyclomatic_complexity`).
+fn is_ignored(attrs: &[syn::Attribute]) -> bool {
+    let marks = |list: &syn::MetaList, is_marker: fn(&syn::Path) -> bool| {
+        list.nested.iter().any(|nested| match nested {
+            syn::NestedMeta::Meta(syn::Meta::Path(path)) => is_marker(path),
+            _ => false,
+        })
+    };
+
+    attrs
+        .iter()
+        .filter_map(|attr| attr.parse_meta().ok())
+        .any(|meta| match meta {
+            syn::Meta::List(list) if list.path.is_ident("complexity") => {
+                marks(&list, |path| path.is_ident("ignore"))
+            }
+            syn::Meta::List(list) if list.path.is_ident("allow") => {
+                marks(&list, is_complexity_lint)
+            }
+            _ => false,
+        })
+}
+
+fn is_complexity_lint(path: &syn::Path) -> bool {
+    let names: Vec<String> = path
+        .segments
+        .iter()
+        .map(|segment| segment.ident.to_string())
+        .collect();
+    matches!(
+        names.as_slice(),
+        [tool, lint] if tool == "clippy"
+            && (lint == "cognitive_complexity" || lint == "cyclomatic_complexity")
+    )
+}
+
+/// Whether `attrs` mark test code (`#[cfg(test)]` items and `#[test]` or
+/// `#[tokio::test]` style functions) that `opts` leave out.
+fn is_skipped_test(attrs: &[syn::Attribute], opts: &AnalyzeOptions) -> bool {
+    let is_test_fn = attrs.iter().any(|attr| {
+        attr.path
+            .segments
+            .last()
+            .is_some_and(|segment| segment.ident == "test")
+    });
+
+    !opts.include_tests && (is_test_fn || is_test_only(attrs))
+}
+
+/// Whether `attrs` restrict an item to test builds: `cfg(test)`,
+/// `cfg(all(test, ..))`, or a `cfg_attr(.., cfg(..))` expanding to either.
+/// `cfg(any(test, ..))` and `cfg_attr(test, ..)` don't, as the item can still
+/// exist outside of tests.
+pub fn is_test_only(attrs: &[syn::Attribute]) -> bool {
+    attrs
+        .iter()
+        .filter_map(|attr| attr.parse_meta().ok())
+        .any(|meta| gates_to_test(&meta))
+}
+
+fn gates_to_test(meta: &syn::Meta) -> bool {
+    let list = match meta {
+        syn::Meta::List(list) => list,
+        _ => return false,
+    };
+
+    if list.path.is_ident("cfg") {
+        list.nested.len() == 1 && list.nested.iter().all(requires_test)
+    } else if list.path.is_ident("cfg_attr") {
+        // the first entry is the predicate, the rest the attributes it adds
+        list.nested.iter().skip(1).any(|nested| match nested {
+            syn::NestedMeta::Meta(meta) => gates_to_test(meta),
+            _ => false,
+        })
+    } else {
+        false
+    }
+}
+
+/// Whether a cfg predicate can only hold in test builds.
+fn requires_test(predicate: &syn::NestedMeta) -> bool {
+    match predicate {
+        syn::NestedMeta::Meta(syn::Meta::Path(path)) => path.is_ident("test"),
+        syn::NestedMeta::Meta(syn::Meta::List(list)) if list.path.is_ident("all") => {
+            list.nested.iter().any(requires_test)
+        }
+        _ => false,
+    }
+}
+
+fn has_attr(attrs: &[syn::Attribute], name: &str) -> bool {
+    attrs.iter().any(|attr| attr.path.is_ident(name))
+}
+
+/// Name of impls whose self type isn't a path, e.g. `impl Foo for &Bar`.
+const UNNAMED_IMPL: &str = "<unnamed>";
+
+/// `Display for Foo` for trait impls, so the impls of a type can be told
+/// apart, and just `Foo` for inherent ones.
+fn impl_label(ast: &syn::ItemImpl) -> String {
+    let self_name = get_impl_resolved_name(ast);
+    match &ast.trait_ {
+        Some((bang, path, _)) => {
+            let negation = if bang.is_some() { "!" } else { "" };
+            format!("{}{} for {}", negation, path_name(path), self_name)
+        }
+        None => self_name,
+    }
+}
+
+/// The self type of an impl as written, e.g. `std::collections::HashMap<K, V>`.
+fn get_impl_resolved_name(ast: &syn::ItemImpl) -> String {
     match &*ast.self_ty {
-        syn::Type::Path(type_path) => Ok(type_path.path.segments[0].ident.clone()),
-        _ => Err(Box::new(
-            ParseError::kind(ParseErrorKind::NoMatches)
-                .msg(String::from("Identifier not found for impl")),
-        )),
+        syn::Type::Path(type_path) if type_path.qself.is_none() => path_name(&type_path.path),
+        _ => String::from(UNNAMED_IMPL),
+    }
+}
+
+fn path_name(path: &syn::Path) -> String {
+    path.segments
+        .iter()
+        .map(|segment| {
+            let mut name = segment.ident.to_string();
+            if let syn::PathArguments::AngleBracketed(generics) = &segment.arguments {
+                let args: Vec<String> = generics.args.iter().map(generic_argument_name).collect();
+                name += &format!("<{}>", args.join(", "));
+            }
+            name
+        })
+        .collect::<Vec<String>>()
+        .join("::")
+}
+
+fn generic_argument_name(arg: &syn::GenericArgument) -> String {
+    match arg {
+        syn::GenericArgument::Lifetime(lifetime) => lifetime.to_string(),
+        syn::GenericArgument::Type(ty) => type_name(ty),
+        syn::GenericArgument::Binding(binding) => {
+            format!("{} = {}", binding.ident, type_name(&binding.ty))
+        }
+        _ => arg.to_token_stream().to_string(),
+    }
+}
+
+fn type_name(ty: &syn::Type) -> String {
+    match ty {
+        syn::Type::Path(type_path) if type_path.qself.is_none() => path_name(&type_path.path),
+        syn::Type::Reference(reference) => {
+            let mut name = String::from("&");
+            if let Some(lifetime) = &reference.lifetime {
+                name += &format!("{} ", lifetime);
+            }
+            if reference.mutability.is_some() {
+                name += "mut ";
+            }
+            name + &type_name(&reference.elem)
+        }
+        syn::Type::Slice(slice) => format!("[{}]", type_name(&slice.elem)),
+        syn::Type::Tuple(tuple) => {
+            let elems: Vec<String> = tuple.elems.iter().map(type_name).collect();
+            format!("({})", elems.join(", "))
+        }
+        _ => ty.to_token_stream().to_string(),
+    }
+}
+
+/// State threaded through the `Process` walk of a single function.
+struct Context<'a> {
+    opts: &'a AnalyzeOptions,
+    depth: usize,
+    /// Set once the walk gave up on a subtree for being nested too deeply.
+    truncated: bool,
+    /// Number of branching constructs enclosing the current expression.
+    nesting: usize,
+    /// Cognitive complexity of the walked function so far.
+    cognitive: usize,
+    /// Set while walking the `if` of an `else if`, which costs no more for
+    /// its nesting than the `if` it continues.
+    else_if: bool,
+    deepest: Option<Nesting>,
+    /// Nodes of the functions declared inside the walked function.
+    nested: Vec<ComplexityNode>,
+}
+
+impl<'a> Context<'a> {
+    fn new(opts: &'a AnalyzeOptions) -> Context<'a> {
+        Context {
+            opts,
+            depth: 0,
+            truncated: false,
+            nesting: 0,
+            cognitive: 0,
+            else_if: false,
+            deepest: None,
+            nested: vec![],
+        }
+    }
+
+    /// Context for a function declared inside the one being walked. It keeps
+    /// the depth, as the walk still recurses on the same stack.
+    fn nested_in(&self) -> Context<'a> {
+        Context {
+            depth: self.depth,
+            ..Context::new(self.opts)
+        }
+    }
+
+    /// Build the node of the function this context walked.
+    fn finish(
+        self,
+        name: String,
+        kind: ComplexityNodeKind,
+        start: LineColumn,
+        complexity: usize,
+    ) -> ComplexityNode {
+        let mut node = ComplexityNode::new(name, kind)
+            .with_complexity(complexity)
+            .with_cognitive(self.cognitive)
+            .with_truncated(self.truncated)
+            .with_position(start.line, start.column + 1);
+        node.deepest = self.deepest;
+        node.children = self.nested;
+        node
+    }
+
+    /// Enter a branching construct, remembering it if it is the deepest yet.
+    fn enter_branch(&mut self, construct: &'static str, span: proc_macro2::Span) {
+        self.nesting += 1;
+        let deeper = match &self.deepest {
+            Some(deepest) => self.nesting > deepest.level,
+            None => true,
+        };
+        if deeper {
+            self.deepest = Some(Nesting {
+                construct,
+                line: span.start().line,
+                level: self.nesting,
+            });
+        }
     }
 }
 
 trait Process {
-    fn process(self) -> usize;
+    fn process(self, ctx: &mut Context) -> usize;
 }
 
 impl Process for syn::Block {
-    fn process(self) -> usize {
+    fn process(self, ctx: &mut Context) -> usize {
         let mut complexity: usize = 0;
         for stmt in self.stmts {
-            match stmt {
-                // syn::Stmt::Local(local) => println!("{:#?}", local),
-                // syn::Stmt::Item(item) => println!("{:#?}", item),
-                syn::Stmt::Expr(inner) => complexity += inner.process(),
-                // syn::Stmt::Semi(expr, semi) => println!("{:#?}, {:#?}", expr, semi),
-                _ => {}
+            let stmt_complexity = match stmt {
+                syn::Stmt::Local(inner) => inner.process(ctx),
+                syn::Stmt::Expr(inner) => inner.process(ctx),
+                syn::Stmt::Semi(inner, _) => inner.process(ctx),
+                // nested functions are children of, not part of, the
+                // function they are declared in
+                syn::Stmt::Item(syn::Item::Fn(inner)) => {
+                    let node = item_fn_node(inner, ctx.nested_in());
+                    ctx.nested.push(node);
+                    0
+                }
+                // `m!(..);` is a Semi expression, but syn 1 parses `m! { .. }` as an item
+                syn::Stmt::Item(syn::Item::Macro(inner)) => inner.mac.process(ctx),
+                _ => 0,
             };
+            complexity = complexity.saturating_add(stmt_complexity);
         }
 
         complexity
     }
 }
 
+impl Process for syn::Local {
+    fn process(self, ctx: &mut Context) -> usize {
+        match self.init {
+            Some((_, init)) => (*init).process(ctx),
+            None => 0,
+        }
+    }
+}
+
 impl Process for syn::Expr {
-    fn process(self) -> usize {
-        let mut complexity: usize = 0;
-        match self {
-            syn::Expr::Array(inner) => complexity += inner.process(),
-            syn::Expr::Assign(inner) => complexity += inner.process(),
-            syn::Expr::AssignOp(inner) => complexity += inner.process(),
-            syn::Expr::Block(inner) => complexity += inner.process(),
-            syn::Expr::Break(inner) => complexity += inner.process(),
-            syn::Expr::If(inner) => complexity += inner.process(),
-            _ => {}
+    /// Every nested expression goes through here, so this is where the walk
+    /// stops descending once `max_depth` is reached instead of overflowing the
+    /// stack on pathological (e.g. machine generated) code.
+    fn process(self, ctx: &mut Context) -> usize {
+        let else_if = std::mem::take(&mut ctx.else_if);
+        if ctx.depth >= ctx.opts.max_depth {
+            ctx.truncated = true;
+            return 0;
+        }
+
+        let branch = match &self {
+            syn::Expr::ForLoop(inner) => Some(("for", inner.for_token.span)),
+            syn::Expr::If(inner) => Some(("if", inner.if_token.span)),
+            syn::Expr::Loop(inner) => Some(("loop", inner.loop_token.span)),
+            syn::Expr::Match(inner) => Some(("match", inner.match_token.span)),
+            syn::Expr::While(inner) => Some(("while", inner.while_token.span)),
+            _ => None,
+        };
+        if let Some((construct, span)) = branch {
+            ctx.enter_branch(construct, span);
+            let cost = if else_if { 1 } else { ctx.nesting };
+            ctx.cognitive = ctx.cognitive.saturating_add(cost);
+        }
+
+        ctx.depth += 1;
+        let complexity = match self {
+            syn::Expr::Array(inner) => inner.process(ctx),
+            syn::Expr::Assign(inner) => inner.process(ctx),
+            syn::Expr::AssignOp(inner) => inner.process(ctx),
+            syn::Expr::Async(inner) => inner.block.process(ctx),
+            syn::Expr::Await(inner) => (*inner.base).process(ctx),
+            syn::Expr::Binary(inner) => inner.process(ctx),
+            syn::Expr::Block(inner) => inner.process(ctx),
+            syn::Expr::Break(inner) => inner.process(ctx),
+            syn::Expr::Call(inner) => inner.process(ctx),
+            syn::Expr::Cast(inner) => (*inner.expr).process(ctx),
+            syn::Expr::Closure(inner) => inner.process(ctx),
+            syn::Expr::Continue(_) => 1,
+            syn::Expr::ForLoop(inner) => inner.process(ctx),
+            syn::Expr::Field(inner) => (*inner.base).process(ctx),
+            syn::Expr::Group(inner) => (*inner.expr).process(ctx),
+            syn::Expr::If(inner) => inner.process(ctx),
+            syn::Expr::Index(inner) => inner.process(ctx),
+            syn::Expr::Let(inner) => inner.process(ctx),
+            syn::Expr::Loop(inner) => inner.process(ctx),
+            syn::Expr::Macro(inner) => inner.process(ctx),
+            syn::Expr::Match(inner) => inner.process(ctx),
+            syn::Expr::MethodCall(inner) => inner.process(ctx),
+            syn::Expr::Paren(inner) => (*inner.expr).process(ctx),
+            syn::Expr::Reference(inner) => (*inner.expr).process(ctx),
+            syn::Expr::Repeat(inner) => inner.process(ctx),
+            syn::Expr::Struct(inner) => inner.process(ctx),
+            syn::Expr::Try(inner) => inner.process(ctx),
+            syn::Expr::Tuple(inner) => inner.process(ctx),
+            syn::Expr::Unary(inner) => (*inner.expr).process(ctx),
+            syn::Expr::While(inner) => inner.process(ctx),
+            _ => 0,
+        };
+        ctx.depth -= 1;
+        if branch.is_some() {
+            ctx.nesting -= 1;
         }
 
         complexity
@@ -166,11 +1088,11 @@ impl Process for syn::Expr {
 }
 
 impl Process for syn::ExprArray {
-    fn process(self) -> usize {
+    fn process(self, ctx: &mut Context) -> usize {
         let mut complexity: usize = 0;
 
         for elem in self.elems {
-            complexity += elem.process();
+            complexity = complexity.saturating_add(elem.process(ctx));
         }
 
         complexity
@@ -178,55 +1100,1310 @@ impl Process for syn::ExprArray {
 }
 
 impl Process for syn::ExprAssign {
-    fn process(self) -> usize {
+    fn process(self, ctx: &mut Context) -> usize {
         let mut complexity: usize = 0;
 
-        complexity += (*(self.left)).process();
-        complexity += (*(self.right)).process();
+        complexity = complexity.saturating_add((*(self.left)).process(ctx));
+        complexity = complexity.saturating_add((*(self.right)).process(ctx));
 
         complexity
     }
 }
 
 impl Process for syn::ExprAssignOp {
-    fn process(self) -> usize {
+    fn process(self, ctx: &mut Context) -> usize {
         let mut complexity: usize = 0;
 
-        complexity += (*(self.left)).process();
-        complexity += (*(self.right)).process();
+        complexity = complexity.saturating_add((*(self.left)).process(ctx));
+        complexity = complexity.saturating_add((*(self.right)).process(ctx));
+
+        complexity
+    }
+}
+
+/// `&&` and `||` short-circuit, so each one is a decision; other operators
+/// only matter through their operands.
+impl Process for syn::ExprBinary {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = match self.op {
+            syn::BinOp::And(_) | syn::BinOp::Or(_) if ctx.opts.counted.count_boolean_operators => 1,
+            _ => 0,
+        };
+        if starts_boolean_run(&self) {
+            ctx.cognitive = ctx.cognitive.saturating_add(1);
+        }
+
+        complexity = complexity.saturating_add((*(self.left)).process(ctx));
+        complexity = complexity.saturating_add((*(self.right)).process(ctx));
 
         complexity
     }
 }
 
+/// Whether `expr` is a `&&` or `||` that doesn't continue a run of the same
+/// operator on its left, as `a && b && c` parses as `(a && b) && c`.
+fn starts_boolean_run(expr: &syn::ExprBinary) -> bool {
+    let continues = |left: &syn::Expr| match left {
+        syn::Expr::Binary(left) => {
+            std::mem::discriminant(&left.op) == std::mem::discriminant(&expr.op)
+        }
+        _ => false,
+    };
+
+    match expr.op {
+        syn::BinOp::And(_) | syn::BinOp::Or(_) => !continues(&expr.left),
+        _ => false,
+    }
+}
+
 impl Process for syn::ExprBlock {
-    fn process(self) -> usize {
-        self.block.process()
+    fn process(self, ctx: &mut Context) -> usize {
+        self.block.process(ctx)
     }
 }
 
+/// A `break` (or `continue`, which has no value to walk) is an exit taken on
+/// some paths only, so it adds one; see `ExprLoop` for the exception.
 impl Process for syn::ExprBreak {
-    fn process(self) -> usize {
+    fn process(self, ctx: &mut Context) -> usize {
         let mut complexity: usize = 1;
 
         if let Some(expr) = self.expr {
-            complexity += (*expr).process();
+            complexity = complexity.saturating_add((*expr).process(ctx));
         }
 
         complexity
     }
 }
 
+impl Process for syn::ExprCall {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = (*self.func).process(ctx);
+
+        for arg in self.args {
+            complexity = complexity.saturating_add(arg.process(ctx));
+        }
+
+        complexity
+    }
+}
+
+/// A closure's branches are attributed to the enclosing function.
+impl Process for syn::ExprClosure {
+    fn process(self, ctx: &mut Context) -> usize {
+        (*self.body).process(ctx)
+    }
+}
+
 impl Process for syn::ExprIf {
-    fn process(self) -> usize {
+    fn process(self, ctx: &mut Context) -> usize {
         let mut complexity: usize = 1;
 
-        complexity += self.then_branch.process();
+        complexity = complexity.saturating_add((*self.cond).process(ctx));
+        complexity = complexity.saturating_add(self.then_branch.process(ctx));
 
         if let Some((_, expr)) = self.else_branch {
-            complexity += (*expr).process();
+            match *expr {
+                syn::Expr::Block(_) => {
+                    ctx.cognitive = ctx.cognitive.saturating_add(1);
+                    complexity = complexity.saturating_add(ctx.opts.weights.if_else);
+                    complexity = complexity.saturating_add((*expr).process(ctx));
+                }
+                // an `else if` continues this chain rather than nesting in it
+                syn::Expr::If(_) => {
+                    ctx.nesting -= 1;
+                    ctx.else_if = true;
+                    complexity = complexity.saturating_add((*expr).process(ctx));
+                    ctx.nesting += 1;
+                }
+                _ => complexity = complexity.saturating_add((*expr).process(ctx)),
+            }
+        }
+
+        complexity
+    }
+}
+
+impl Process for syn::ExprMacro {
+    fn process(self, ctx: &mut Context) -> usize {
+        self.mac.process(ctx)
+    }
+}
+
+/// Macros are opaque token streams, so only these are looked into:
+///
+/// - `matches!(expr, pat)` is a `match` in disguise: one per `|` alternative
+///   of the pattern, one more for an `if` guard, plus whatever the expression
+///   and guard contain.
+/// - `assert!`, `assert_eq!`, `assert_ne!` and their `debug_` variants: one
+///   for the panic, plus whatever their arguments contain.
+///
+/// Every other macro adds nothing, and so do the above if their arguments
+/// don't parse, beyond their own one.
+impl Process for syn::Macro {
+    fn process(self, ctx: &mut Context) -> usize {
+        let name = match self.path.get_ident() {
+            Some(ident) => ident.to_string(),
+            None => return 0,
+        };
+
+        match name.as_str() {
+            "matches" => match parse_matches.parse2(self.tokens) {
+                Ok((expr, alternatives, guard)) => {
+                    let mut complexity: usize = expr.process(ctx);
+                    if ctx.opts.counted.count_match_arms {
+                        complexity = complexity.saturating_add(alternatives);
+                    } else {
+                        complexity = complexity.saturating_add(1);
+                    }
+                    if let Some(guard) = guard {
+                        complexity = complexity.saturating_add(1);
+                        complexity = complexity.saturating_add(guard.process(ctx));
+                    }
+                    complexity
+                }
+                Err(_) => 1,
+            },
+            "assert" | "assert_eq" | "assert_ne" | "debug_assert" | "debug_assert_eq"
+            | "debug_assert_ne" => {
+                let args = Punctuated::<syn::Expr, syn::Token![,]>::parse_terminated;
+                let mut complexity: usize = 1;
+                for arg in args.parse2(self.tokens).into_iter().flatten() {
+                    complexity = complexity.saturating_add(arg.process(ctx));
+                }
+                complexity
+            }
+            _ => 0,
+        }
+    }
+}
+
+/// The arguments of `matches!`: the expression, how many alternatives the
+/// pattern has and the guard.
+fn parse_matches(input: ParseStream) -> syn::Result<(syn::Expr, usize, Option<syn::Expr>)> {
+    let expr: syn::Expr = input.parse()?;
+    input.parse::<syn::Token![,]>()?;
+
+    input.parse::<Option<syn::Token![|]>>()?;
+    let mut alternatives: usize = 0;
+    loop {
+        input.parse::<syn::Pat>()?;
+        alternatives += 1;
+        if input.parse::<Option<syn::Token![|]>>()?.is_none() {
+            break;
+        }
+    }
+
+    let guard = if input.parse::<Option<syn::Token![if]>>()?.is_some() {
+        Some(input.parse()?)
+    } else {
+        None
+    };
+    input.parse::<Option<syn::Token![,]>>()?;
+
+    Ok((expr, alternatives, guard))
+}
+
+impl Process for syn::ExprMethodCall {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = (*self.receiver).process(ctx);
+
+        for arg in self.args {
+            complexity = complexity.saturating_add(arg.process(ctx));
+        }
+
+        complexity
+    }
+}
+
+impl Process for syn::ExprIndex {
+    fn process(self, ctx: &mut Context) -> usize {
+        let complexity: usize = (*self.expr).process(ctx);
+
+        complexity.saturating_add((*self.index).process(ctx))
+    }
+}
+
+impl Process for syn::ExprRepeat {
+    fn process(self, ctx: &mut Context) -> usize {
+        let complexity: usize = (*self.expr).process(ctx);
+
+        complexity.saturating_add((*self.len).process(ctx))
+    }
+}
+
+impl Process for syn::ExprStruct {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = 0;
+
+        for field in self.fields {
+            complexity = complexity.saturating_add(field.expr.process(ctx));
+        }
+        if let Some(rest) = self.rest {
+            complexity = complexity.saturating_add((*rest).process(ctx));
+        }
+
+        complexity
+    }
+}
+
+impl Process for syn::ExprTuple {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = 0;
+
+        for elem in self.elems {
+            complexity = complexity.saturating_add(elem.process(ctx));
+        }
+
+        complexity
+    }
+}
+
+/// `?` is an early return, i.e. an implicit branch.
+impl Process for syn::ExprTry {
+    fn process(self, ctx: &mut Context) -> usize {
+        let complexity: usize = usize::from(ctx.opts.counted.count_try);
+
+        complexity.saturating_add((*self.expr).process(ctx))
+    }
+}
+
+impl Process for syn::ExprMatch {
+    fn process(self, ctx: &mut Context) -> usize {
+        // the scrutinee itself may branch before any arm is picked.
+        let mut complexity: usize = (*self.expr).process(ctx);
+
+        // like an `if`, the first arm is the path taken anyway and every
+        // other arm adds one. A guard is a decision of its own, even on a
+        // catch-all `_` arm, and both guards and bodies may hold further
+        // branching (e.g. nested matches in a state machine).
+        let count_arms = ctx.opts.counted.count_match_arms;
+        for (index, arm) in self.arms.into_iter().enumerate() {
+            if index > 0 && (count_arms || index == 1) {
+                complexity = complexity.saturating_add(1);
+            }
+            if let Some((_, guard)) = arm.guard {
+                complexity = complexity.saturating_add(1);
+                complexity = complexity.saturating_add((*guard).process(ctx));
+            }
+            complexity = complexity.saturating_add((*arm.body).process(ctx));
+        }
+
+        complexity
+    }
+}
+
+/// The `let` of `if let` and `while let` is a pattern match on top of the
+/// `if`/`while` itself: the pattern may not match.
+impl Process for syn::ExprLet {
+    fn process(self, ctx: &mut Context) -> usize {
+        let complexity: usize = 1;
+
+        complexity.saturating_add((*self.expr).process(ctx))
+    }
+}
+
+/// Loops, with their exits, count as:
+///
+/// - the loop itself adds one, like `while` and `for`.
+/// - every `break` or `continue` inside adds one, labelled or not.
+/// - except a `break` or `continue` ending the loop body, which is always
+///   taken and so isn't a decision: `loop { work(); break; }` is just 1.
+impl Process for syn::ExprLoop {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = 1;
+
+        let trailing_exit = match self.body.stmts.last() {
+            Some(syn::Stmt::Expr(expr)) | Some(syn::Stmt::Semi(expr, _)) => {
+                matches!(expr, syn::Expr::Break(_) | syn::Expr::Continue(_))
+            }
+            _ => false,
+        };
+
+        complexity = complexity.saturating_add(self.body.process(ctx));
+        if trailing_exit {
+            complexity = complexity.saturating_sub(1);
+        }
+
+        complexity
+    }
+}
+
+/// Like a `while`, whether there is a next item is decided every iteration.
+/// The iterator expression is evaluated once but may still branch, e.g. in
+/// closures passed to adapters.
+impl Process for syn::ExprForLoop {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = 1;
+
+        complexity = complexity.saturating_add((*self.expr).process(ctx));
+        complexity = complexity.saturating_add(self.body.process(ctx));
+
+        complexity
+    }
+}
+
+/// The loop condition is a decision, checked on every iteration.
+impl Process for syn::ExprWhile {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = 1;
+
+        complexity = complexity.saturating_add((*self.cond).process(ctx));
+        complexity = complexity.saturating_add(self.body.process(ctx));
+
+        complexity
+    }
+}
+
+/// Signatures only contribute through expressions hidden in their types, such
+/// as array lengths and const generic arguments.
+impl Process for syn::Signature {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = 0;
+
+        for input in self.inputs {
+            if let syn::FnArg::Typed(pat_type) = input {
+                complexity = complexity.saturating_add((*pat_type.ty).process(ctx));
+            }
+        }
+
+        if let syn::ReturnType::Type(_, ty) = self.output {
+            complexity = complexity.saturating_add((*ty).process(ctx));
+        }
+
+        complexity
+    }
+}
+
+impl Process for syn::Type {
+    fn process(self, ctx: &mut Context) -> usize {
+        match self {
+            syn::Type::Array(inner) => (*inner.elem)
+                .process(ctx)
+                .saturating_add(inner.len.process(ctx)),
+            syn::Type::Group(inner) => (*inner.elem).process(ctx),
+            syn::Type::Paren(inner) => (*inner.elem).process(ctx),
+            syn::Type::Path(inner) => inner.path.process(ctx),
+            syn::Type::Ptr(inner) => (*inner.elem).process(ctx),
+            syn::Type::Reference(inner) => (*inner.elem).process(ctx),
+            syn::Type::Slice(inner) => (*inner.elem).process(ctx),
+            syn::Type::Tuple(inner) => inner
+                .elems
+                .into_iter()
+                .fold(0, |acc, elem| acc.saturating_add(elem.process(ctx))),
+            _ => 0,
+        }
+    }
+}
+
+impl Process for syn::Path {
+    fn process(self, ctx: &mut Context) -> usize {
+        let mut complexity: usize = 0;
+
+        for segment in self.segments {
+            if let syn::PathArguments::AngleBracketed(arguments) = segment.arguments {
+                for arg in arguments.args {
+                    let arg_complexity = match arg {
+                        syn::GenericArgument::Type(ty) => ty.process(ctx),
+                        syn::GenericArgument::Const(expr) => expr.process(ctx),
+                        syn::GenericArgument::Binding(binding) => binding.ty.process(ctx),
+                        _ => 0,
+                    };
+                    complexity = complexity.saturating_add(arg_complexity);
+                }
+            }
         }
 
         complexity
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        is_test_only, process_file, ComplexityConfig, ComplexityNode, ComplexityNodeKind,
+        ComplexityTree, Metric, Nesting, Weights,
+    };
+    use crate::analyzer::AnalyzeOptions;
+    use crate::discovery::ScanOptions;
+    use crate::parsers::error::ParseErrorKind;
+    use rstest::rstest;
+
+    fn complexity(src: &str) -> usize {
+        complexity_with_weights(src, &Weights::default())
+    }
+
+    fn complexity_with_weights(src: &str, weights: &Weights) -> usize {
+        let opts = AnalyzeOptions {
+            weights: weights.clone(),
+            ..AnalyzeOptions::default()
+        };
+
+        tree_with_options(src, &opts).children[0].complexity
+    }
+
+    fn tree_with_options(src: &str, opts: &AnalyzeOptions) -> ComplexityNode {
+        let ast: syn::File = syn::parse_str(src).unwrap();
+        let mut root = ComplexityNode::new(String::from("test"), ComplexityNodeKind::File);
+        process_file(ast, &mut root, opts, None);
+
+        root
+    }
+
+    #[test]
+    fn match_counts_arms_beyond_the_first() {
+        let src = "fn f() { match a { 1 => {} 2 => {} _ => {} } }";
+        assert_eq!(2, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() { match a { _ => {} } }", 0)]
+    #[case("fn f() { match a { _ if b => {} } }", 1)]
+    #[case("fn f() { match a { 1 if b => {} _ if c => {} } }", 3)]
+    fn match_guards_count_even_on_catch_alls(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[test]
+    fn nested_match_accumulates_both_levels() {
+        let src = "fn f() {
+            match a {
+                1 => match b {
+                    1 => {}
+                    2 => {}
+                    _ => {}
+                },
+                _ => {}
+            }
+        }";
+        assert_eq!(3, complexity(src));
+    }
+
+    #[test]
+    fn match_scrutinee_branching_is_counted() {
+        let src = "fn f() { match (if a { 0 } else { 1 }) { 0 => {} _ => {} } }";
+        assert_eq!(2, complexity(src));
+    }
+
+    #[test]
+    fn match_guard_branching_is_counted() {
+        let src = "fn f() { match x { Some(_) if a && b => if c {} } }";
+        assert_eq!(3, complexity(src));
+    }
+
+    #[test]
+    fn while_inside_if() {
+        let src = "fn f() {
+            if a {
+                while b && c {
+                    if d {}
+                }
+            }
+        }";
+        assert_eq!(4, complexity(src));
+    }
+
+    #[rstest]
+    #[case("async fn f() {}", 0)]
+    #[case("async fn f() -> u8 { if a { get().await } else { 0 } }", 1)]
+    #[case("async fn f() -> u8 { pick(a || b).await? }", 2)]
+    #[case("fn f() -> impl Future { async move { if a { x?; } } }", 2)]
+    fn async_code(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() -> Foo { Foo { a: if x { 1 } else { 2 }, b: y? } }", 2)]
+    #[case("fn f() -> Foo { Foo { a: 1, ..if x { d() } else { e() } } }", 1)]
+    #[case("fn f() -> (u8, u8) { (if x { 1 } else { 2 }, a || b) }", 2)]
+    #[case("fn f() -> u8 { v[if x { 0 } else { 1 }] }", 1)]
+    #[case("fn f() -> u8 { lookup(a)?[b?] }", 2)]
+    #[case("fn f() -> u8 { get(x)?.field }", 1)]
+    #[case("fn f() -> [u8; 4] { [if x { 1 } else { 2 }; 4] }", 1)]
+    fn compound_expressions_are_walked(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[rstest]
+    #[case(
+        "fn f() { data.iter().map(|x| match x { A => 1, B => 2, _ => 3 }); }",
+        2
+    )]
+    #[case("fn f() { call(if a { 1 } else { 2 }, b || c); }", 2)]
+    #[case("fn f() { (if a { g } else { h })(x); }", 1)]
+    #[case("fn f() { make(a?).with(b?).build(); }", 2)]
+    #[case("fn f() { plain(a, b.c(), d); }", 0)]
+    fn call_arguments_are_walked(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() -> bool { !(if a { b } else { c }) }", 1)]
+    #[case("fn f() -> i32 { -match a { _ if b => 1, _ => 2 } }", 2)]
+    #[case("fn f() -> &bool { &(a || b) }", 1)]
+    #[case("fn f() { take(&mut if a { x } else { y }); }", 1)]
+    #[case("fn f() -> u8 { (if a { x } else { y }) as u8 }", 1)]
+    #[case("fn f() -> u8 { *(x?) }", 1)]
+    fn wrapper_expressions_pass_through(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() { if x > 0 {} }", 1)]
+    #[case("fn f() { if let Some(x) = opt {} }", 2)]
+    #[case("fn f() { if let Some(x) = opt {} else if let Ok(y) = res {} }", 4)]
+    #[case("fn f() { while let Some(x) = stack.pop() {} }", 2)]
+    #[case("fn f() { if let Some(x) = if a { b } else { c } {} }", 3)]
+    fn let_conditions(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() { loop {} }", 1)]
+    #[case("fn f() { loop { work(); break; } }", 1)]
+    #[case("fn f() -> u8 { loop { break if a { 1 } else { 2 }; } }", 2)]
+    #[case("fn f() { loop { if a { break; } work(); } }", 3)]
+    #[case(
+        "fn f() { 'outer: loop { loop { if a { break 'outer; } continue; } } }",
+        4
+    )]
+    #[case("fn f() { for x in xs { if x { continue; } work(); } }", 3)]
+    fn loops_and_their_exits(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() { for x in xs {} }", 1)]
+    #[case("fn f() { for x in xs { if x {} } }", 2)]
+    #[case("fn f() { for x in xs.iter().filter(|x| x.a || x.b) {} }", 2)]
+    fn for_loops(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f(x: [u8; if cfg!(x) { 4 } else { 8 }]) {}")]
+    #[case("fn f() -> Option<[u8; if cfg!(x) { 4 } else { 8 }]> { None }")]
+    #[case("fn f(x: &Foo<{ if A { 1 } else { 2 } }>) {}")]
+    fn branchy_types_are_counted(#[case] src: &str) {
+        assert_eq!(1, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() { if a && b || c {} }", 3)]
+    #[case("fn f() -> bool { a && (b || c) && d }", 3)]
+    #[case("fn f() -> bool { (a || (b && (c || d))) && e }", 4)]
+    #[case("fn f() -> bool { a + b * c > d - e == f }", 0)]
+    #[case("fn f() { x &= a & b | c; }", 0)]
+    fn short_circuit_operators(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[test]
+    fn logic_in_statements_is_counted() {
+        let src = "fn f() -> Result<(), E> {
+            let x = if a { 1 } else { 2 };
+            let y;
+            if b { work(); };
+            match x { 1 => {} _ => {} };
+            for _ in 0..x { y = x; };
+            check(x)?;
+            Ok(())
+        }";
+        assert_eq!(5, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() -> Result<(), E> { foo()?; bar()?; Ok(()) }", 2)]
+    #[case("fn f() -> Option<u8> { a()?.b()?.c() }", 2)]
+    #[case("fn f() -> Result<u8, E> { Ok(parse(if a { x } else { y })?) }", 2)]
+    fn try_operator_is_an_early_return(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[test]
+    fn closure_branches_belong_to_the_enclosing_function() {
+        let src = "fn f(v: Vec<i32>) -> usize {
+            v.iter().filter(|x| if **x > 0 { true } else { x.is_even() }).count()
+        }";
+        let root = tree_with_options(src, &AnalyzeOptions::default());
+
+        assert_eq!(1, root.children[0].complexity);
+        assert!(root.children[0].children.is_empty());
+
+        let src = "fn f() { apply(|x| match x { 0 => a, _ => b }); }";
+        assert_eq!(1, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() -> bool { matches!(x, Some(_)) }", 1)]
+    #[case("fn f() -> bool { matches!(x, Some(_) | None) }", 2)]
+    #[case("fn f() -> bool { matches!(x, | 'a'..='z' | 'A'..='Z' | '_',) }", 3)]
+    #[case("fn f() -> bool { matches!(x, Some(y) if y > 0 && y < 9) }", 3)]
+    #[case("fn f() -> bool { matches!(x?, (A | B, _)) }", 2)]
+    #[case("fn f() { assert!(a && b); }", 2)]
+    #[case("fn f() { assert_eq!(a, b, \"{}\", c?); debug_assert_ne!(a, 0); }", 3)]
+    #[case("fn f() { debug_assert!(matches!(x, A | B), \"message\"); }", 3)]
+    #[case("fn f() { println!(\"{}\", if a { 1 } else { 2 }); }", 0)]
+    fn branching_macros(#[case] src: &str, #[case] expected: usize) {
+        assert_eq!(expected, complexity(src));
+    }
+
+    #[test]
+    fn matches_macros_combined_with_boolean_operators() {
+        let src = "fn f() -> bool { matches!(x, A) || matches!(y, B) }";
+        assert_eq!(3, complexity(src));
+
+        let src = "fn f() { if matches!(x, A) || matches!(y, B) {} }";
+        assert_eq!(4, complexity(src));
+
+        let src = "fn f() { if matches!(x, A) && y > 0 {} }";
+        assert_eq!(3, complexity(src));
+    }
+
+    #[test]
+    fn fallible_closures_in_iterator_chains() {
+        let src = "fn f(items: &[&str]) -> Result<usize, E> {
+            items.iter().map(|x| x.parse::<i32>()).collect::<Result<Vec<_>, _>>()?;
+            items
+                .iter()
+                .map(|x| if x.is_empty() { Ok(0) } else { Ok(x.parse::<i32>()? * 2) })
+                .collect::<Result<Vec<_>, _>>()?;
+            Ok(items.len())
+        }";
+        // two outer `?`, plus the closure's `if` and `?`
+        assert_eq!(4, complexity(src));
+    }
+
+    #[test]
+    fn automatically_derived_impls_are_skipped_by_default() {
+        let src = "#[automatically_derived]
+            impl Clone for Foo {
+                fn clone(&self) -> Self { if a { Foo } else { Foo } }
+            }
+            fn f() {}";
+
+        let root = tree_with_options(src, &AnalyzeOptions::default());
+        assert_eq!(1, root.children.len());
+        assert_eq!("f", root.children[0].name);
+
+        let opts = AnalyzeOptions {
+            include_derived: true,
+            ..AnalyzeOptions::default()
+        };
+        let root = tree_with_options(src, &opts);
+        assert_eq!(2, root.children.len());
+        assert_eq!("Clone for Foo", root.children[0].name);
+        assert_eq!(1, root.children[0].children[0].complexity);
+    }
+
+    #[test]
+    fn test_code_is_skipped_by_default() {
+        let src = "fn f() {}
+            #[test]
+            fn plain() { assert!(a || b); }
+            #[tokio::test]
+            async fn runtime() {}
+            impl Foo {
+                #[cfg(test)]
+                fn helper() {}
+                fn g() {}
+            }
+            #[cfg_attr(test, derive(Debug))]
+            struct Bar;
+            #[cfg(test)]
+            mod tests {
+                fn h() {}
+            }";
+
+        let names = |root: &ComplexityNode| -> Vec<String> {
+            root.children
+                .iter()
+                .map(|child| child.name.clone())
+                .collect()
+        };
+
+        let root = tree_with_options(src, &AnalyzeOptions::default());
+        assert_eq!(vec!["f", "Foo"], names(&root));
+        assert_eq!(vec!["g"], names(&root.children[1]));
+
+        let opts = AnalyzeOptions {
+            include_tests: true,
+            ..AnalyzeOptions::default()
+        };
+        let root = tree_with_options(src, &opts);
+        assert_eq!(vec!["f", "plain", "runtime", "Foo", "tests"], names(&root));
+        assert_eq!(vec!["helper", "g"], names(&root.children[3]));
+    }
+
+    #[rstest]
+    #[case("#![cfg(test)]", false)]
+    #[case("#![cfg(all(test, unix))]", false)]
+    #[case("#![cfg_attr(feature = \"x\", cfg(test))]", false)]
+    #[case("#![cfg(any(test, unix))]", true)]
+    #[case("#![cfg_attr(test, allow(dead_code))]", true)]
+    #[case("#![allow(dead_code)]", true)]
+    fn test_only_files_are_skipped(#[case] attr: &str, #[case] analyzed: bool) {
+        let src = format!("{}\nfn f() {{ if a {{}} }}", attr);
+
+        let root = tree_with_options(&src, &AnalyzeOptions::default());
+        assert_eq!(analyzed, !root.children.is_empty());
+
+        let opts = AnalyzeOptions {
+            include_tests: true,
+            ..AnalyzeOptions::default()
+        };
+        assert_eq!(1, tree_with_options(&src, &opts).children.len());
+    }
+
+    #[test]
+    fn functions_can_opt_out() {
+        let src = "#[complexity(ignore)]
+            fn machine() { if a {} }
+            #[allow(clippy::cyclomatic_complexity)]
+            fn old_lint() {}
+            #[allow(dead_code, clippy::cognitive_complexity)]
+            fn new_lint() {}
+            #[allow(clippy::too_many_lines)]
+            fn f() {}
+            impl Foo {
+                #[complexity(ignore)]
+                fn g() {}
+                fn h() {}
+            }
+            trait Bar {
+                #[complexity(ignore)]
+                fn i() {}
+                fn j() {}
+            }";
+
+        let tree = ComplexityTree::from_source(String::from("test"), src).unwrap();
+        let functions: Vec<String> = tree.functions().map(|(path, _)| path).collect();
+        assert_eq!(
+            vec!["Fn: f", "Impl: Foo > Method: h", "Trait: Bar > Method: j"],
+            functions
+        );
+    }
+
+    #[test]
+    fn deep_nesting_is_truncated_instead_of_overflowing() {
+        let depth = 300;
+        let src = format!(
+            "fn f() {{ {} {} }}",
+            "if a { ".repeat(depth),
+            "}".repeat(depth)
+        );
+
+        // syn's own parser needs more than the default test stack at this depth
+        let root = std::thread::Builder::new()
+            .stack_size(32 * 1024 * 1024)
+            .spawn(move || {
+                let opts = AnalyzeOptions {
+                    max_depth: 64,
+                    ..AnalyzeOptions::default()
+                };
+                tree_with_options(&src, &opts)
+            })
+            .unwrap()
+            .join()
+            .unwrap();
+
+        assert!(root.children[0].truncated);
+        // only the ifs above the cut-off are counted
+        assert_eq!(64, root.children[0].complexity);
+
+        assert!(
+            !tree_with_options("fn f() { if a { if b {} } }", &AnalyzeOptions::default()).children
+                [0]
+            .truncated
+        );
+    }
+
+    #[rstest]
+    #[case("fn f() { if a { if b {} } }")]
+    #[case("fn f() { if a { foo(); if b {} } }")]
+    #[case("fn f() { if a { if b {} bar() } }")]
+    #[case("fn f() { if a { if b {}; } }")]
+    #[case("fn f() { if c {} else { if b {} } }")]
+    fn nested_if_counts_both(#[case] src: &str) {
+        assert_eq!(2, complexity(src));
+    }
+
+    #[rstest]
+    #[case("fn f() {}", 0)]
+    #[case("fn f() { if a {} }", 1)]
+    #[case("fn f() { for x in y { if a { while b {} } } }", 6)]
+    #[case("fn f() { if a {} else if b {} else {} }", 3)]
+    #[case("fn f() { if a { if b {} else if c {} } }", 4)]
+    #[case("fn f() { if a && b && c {} }", 2)]
+    #[case("fn f() { if a && b || c {} }", 3)]
+    #[case("fn f() { match a { 1 => {} 2 => {} _ => {} } }", 1)]
+    #[case("fn f() { loop { if a { break; } } }", 3)]
+    #[case("fn f() -> R { g()?; Ok(()) }", 0)]
+    fn cognitive_complexity_weighs_nesting(#[case] src: &str, #[case] expected: usize) {
+        let root = tree_with_options(src, &AnalyzeOptions::default());
+        assert_eq!(expected, root.children[0].cognitive);
+    }
+
+    #[test]
+    fn use_metric_replaces_function_complexities() {
+        let src = "fn f() { if a { if b {} } } impl Foo { fn g() { if a && b {} } }";
+        let mut tree = ComplexityTree::from_source(String::from("test"), src).unwrap();
+
+        tree.use_metric(Metric::Cyclomatic);
+        assert_eq!(2, tree.root.children[0].complexity);
+        tree.use_metric(Metric::Cognitive);
+        let complexities: Vec<usize> = tree.nodes().map(|(_, node)| node.complexity).collect();
+        assert_eq!(vec![3, 0, 2], complexities);
+    }
+
+    #[rstest]
+    #[case("fn f() { if a && b || c {} }", 3, 1)]
+    #[case("fn f() -> R { g()?.h()?; Ok(()) }", 2, 0)]
+    #[case("fn f() { match a { 1 => {} 2 => {} _ => {} } }", 2, 1)]
+    #[case("fn f() { match a { _ => {} } }", 0, 0)]
+    #[case("fn f() { match a { 1 if b => {} _ => {} } }", 2, 2)]
+    #[case("fn f() -> bool { matches!(a, 1 | 2 | 3) }", 3, 1)]
+    fn constructs_can_be_left_out(#[case] src: &str, #[case] all: usize, #[case] none: usize) {
+        let counted = |counted: ComplexityConfig| {
+            let opts = AnalyzeOptions {
+                counted,
+                ..AnalyzeOptions::default()
+            };
+            tree_with_options(src, &opts).children[0].complexity
+        };
+
+        assert_eq!(all, counted(ComplexityConfig::default()));
+        let none_counted = ComplexityConfig {
+            count_boolean_operators: false,
+            count_match_arms: false,
+            count_try: false,
+        };
+        assert_eq!(none, counted(none_counted));
+    }
+
+    #[rstest]
+    #[case("fn f() { if a {} }", 0, 1)]
+    #[case("fn f() { if a {} }", 1, 1)]
+    #[case("fn f() { if a {} else {} }", 0, 1)]
+    #[case("fn f() { if a {} else {} }", 1, 2)]
+    #[case("fn f() { if a {} else if b {} }", 1, 2)]
+    fn if_else_weight(#[case] src: &str, #[case] if_else: usize, #[case] expected: usize) {
+        let weights = Weights { if_else };
+        assert_eq!(expected, complexity_with_weights(src, &weights));
+    }
+
+    #[test]
+    fn statement_position_macros() {
+        let src = "fn f() {
+            matches!(x, A | B);
+            matches! { y, C }
+            write!(out, \"{}\", x)?;
+            todo!();
+        }";
+        assert_eq!(4, complexity(src));
+    }
+
+    #[test]
+    fn nested_functions_are_children() {
+        let src = "fn outer() {
+            fn inner(a: bool) -> bool {
+                fn innermost() { x?; }
+                a || b
+            }
+            if inner(c) {}
+        }";
+        let tree = ComplexityTree::from_source_with_options(
+            String::from("lib.rs"),
+            src,
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+
+        let functions: Vec<(String, usize)> = tree
+            .functions()
+            .map(|(path, node)| (path, node.complexity))
+            .collect();
+        assert_eq!(
+            vec![
+                (String::from("Fn: outer"), 1),
+                (String::from("Fn: outer > Fn: inner"), 1),
+                (String::from("Fn: outer > Fn: inner > Fn: innermost"), 1),
+            ],
+            functions
+        );
+    }
+
+    #[test]
+    fn records_line_and_deepest_branch() {
+        let src = "fn f() {
+            if a {
+            } else if b {
+            } else if c {
+                match d {
+                    _ => {}
+                }
+            }
+        }";
+        let f = &tree_with_options(src, &AnalyzeOptions::default()).children[0];
+
+        assert_eq!((1, 4), (f.line, f.column));
+        assert_eq!(
+            Some(Nesting {
+                construct: "match",
+                line: 5,
+                level: 2,
+            }),
+            f.deepest
+        );
+    }
+
+    #[test]
+    fn functions_yields_breadcrumbs_of_fns_and_methods() {
+        let src = "
+            fn free() { if a {} }
+            struct Foo;
+            impl Foo { fn bar() {} fn baz() { a?; } }
+            impl Foo {}
+        ";
+        let tree = ComplexityTree::from_source_with_options(
+            String::from("lib.rs"),
+            src,
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+
+        let functions: Vec<(String, String, usize)> = tree
+            .functions()
+            .map(|(path, node)| (path, node.kind.to_string(), node.complexity))
+            .collect();
+        assert_eq!(
+            vec![
+                (String::from("Fn: free"), String::from("Fn"), 1),
+                (
+                    String::from("Impl: Foo > Method: bar"),
+                    String::from("Method"),
+                    0
+                ),
+                (
+                    String::from("Impl: Foo > Method: baz"),
+                    String::from("Method"),
+                    1
+                ),
+            ],
+            functions
+        );
+    }
+
+    #[test]
+    fn hide_private_keeps_the_api_surface() {
+        let src = "
+            pub fn api() {}
+            fn helper() {}
+            pub(crate) fn internal() {}
+            impl Foo { pub fn new() {} fn private() {} }
+            impl Display for Foo { fn fmt() {} }
+        ";
+        let mut tree = ComplexityTree::from_source_with_options(
+            String::from("lib.rs"),
+            src,
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+        tree.hide_private();
+
+        let paths: Vec<String> = tree.functions().map(|(path, _)| path).collect();
+        assert_eq!(
+            vec![
+                "Fn: api",
+                "Impl: Foo > Method: new",
+                "Impl: Display for Foo > Method: fmt"
+            ],
+            paths
+        );
+    }
+
+    #[test]
+    fn hidden_functions_still_count_in_totals() {
+        let src = "
+            pub fn a() { if x {} }
+            fn b() { if x {} if y {} }
+            impl S { pub fn c() { if x {} } fn d() { if x {} if y {} } }
+        ";
+        let mut tree = ComplexityTree::from_source_with_options(
+            String::from("lib.rs"),
+            src,
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+        tree.hide_private();
+
+        let nodes: Vec<(String, usize)> = tree
+            .nodes()
+            .map(|(path, node)| (path, node.total()))
+            .collect();
+        assert_eq!(
+            vec![
+                (String::from("Fn: a"), 1),
+                (String::from("Impl: S"), 3),
+                (String::from("Impl: S > Method: c"), 1),
+            ],
+            nodes
+        );
+        assert_eq!(6, tree.root.total());
+    }
+
+    #[test]
+    fn total_sums_the_subtree() {
+        let src = "
+            fn free() { if a {} }
+            impl Foo {
+                fn bar() { a?; b?; }
+                fn baz() { fn inner() { if a {} } if b {} }
+            }
+        ";
+        let root = tree_with_options(src, &AnalyzeOptions::default());
+
+        assert_eq!(1, root.children[0].total());
+        assert_eq!(2, root.children[1].children[0].total());
+        assert_eq!(2, root.children[1].children[1].total());
+        assert_eq!(4, root.children[1].total());
+        assert_eq!(5, root.total());
+    }
+
+    #[rstest]
+    #[case("impl Foo {}", "Foo")]
+    #[case(
+        "impl Foo for std::collections::HashMap<K, V> {}",
+        "Foo for std::collections::HashMap<K, V>"
+    )]
+    #[case("impl<T> Wrapper<T> {}", "Wrapper<T>")]
+    #[case(
+        "impl<'a> Parser<'a, &'a mut [u8], (u8, u16)> {}",
+        "Parser<'a, &'a mut [u8], (u8, u16)>"
+    )]
+    #[case("impl Counter<Item = u8> {}", "Counter<Item = u8>")]
+    #[case("impl fmt::Display for Foo {}", "fmt::Display for Foo")]
+    #[case("impl<T> From<T> for Foo<T> {}", "From<T> for Foo<T>")]
+    #[case("impl !Send for Foo {}", "!Send for Foo")]
+    #[case("impl Foo for &Bar {}", "Foo for <unnamed>")]
+    #[case("impl Foo for (A, B) {}", "Foo for <unnamed>")]
+    fn impl_names_keep_the_full_self_type(#[case] src: &str, #[case] expected: &str) {
+        let root = tree_with_options(src, &AnalyzeOptions::default());
+        assert_eq!(expected, root.children[0].name);
+    }
+
+    #[test]
+    fn trait_default_methods_are_children_of_the_trait() {
+        let src = "
+            pub trait Greeter {
+                fn greet(&self) { if a && b {} }
+                fn name(&self) -> String;
+                fn describe(&self) -> String { self.name()? }
+                const ID: u8 = 0;
+            }
+            trait Marker {}
+        ";
+        let root = tree_with_options(src, &AnalyzeOptions::default());
+        assert_eq!(2, root.children.len());
+        assert_eq!(ComplexityNodeKind::Trait, root.children[1].kind);
+        assert!(root.children[1].children.is_empty());
+
+        let greeter = &root.children[0];
+        assert_eq!(ComplexityNodeKind::Trait, greeter.kind);
+        assert_eq!("Greeter", greeter.name);
+        let methods: Vec<(&str, usize, bool)> = greeter
+            .children
+            .iter()
+            .map(|method| (method.name.as_str(), method.complexity, method.public))
+            .collect();
+        assert_eq!(vec![("greet", 2, true), ("describe", 1, true)], methods);
+    }
+
+    #[test]
+    fn inline_modules_are_children_of_the_file() {
+        let src = "
+            mod declared;
+            mod outer {
+                fn f() { if a {} }
+                mod inner {
+                    impl Foo { fn g() { if a || b {} } }
+                }
+            }
+        ";
+        let tree = ComplexityTree::from_source_with_options(
+            String::from("test"),
+            src,
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(1, tree.root.children.len());
+        assert_eq!(ComplexityNodeKind::Mod, tree.root.children[0].kind);
+
+        let functions: Vec<(String, usize)> = tree
+            .functions()
+            .map(|(path, node)| (path, node.complexity))
+            .collect();
+        assert_eq!(
+            vec![
+                (String::from("Mod: outer > Fn: f"), 1),
+                (
+                    String::from("Mod: outer > Mod: inner > Impl: Foo > Method: g"),
+                    2
+                ),
+            ],
+            functions
+        );
+    }
+
+    #[rstest]
+    #[case(false, vec![("describe", 1), ("name", 0)])]
+    #[case(true, vec![("describe", 1), ("greet", 2), ("name", 0)])]
+    fn resolve_trait_defaults(#[case] resolve: bool, #[case] methods: Vec<(&str, usize)>) {
+        let src = "
+            trait Greeter {
+                fn greet(&self) { if a && b {} }
+                fn describe(&self) { if a {} }
+                fn name(&self);
+            }
+            impl Greeter for Foo {
+                fn describe(&self) { a?; }
+                fn name(&self) {}
+            }
+        ";
+        let opts = AnalyzeOptions {
+            resolve_trait_defaults: resolve,
+            ..AnalyzeOptions::default()
+        };
+        let imp = &tree_with_options(src, &opts).children[1];
+
+        let mut found: Vec<(&str, usize)> = imp
+            .children
+            .iter()
+            .map(|method| (method.name.as_str(), method.complexity))
+            .collect();
+        found.sort();
+        assert_eq!(methods, found);
+    }
+
+    #[rstest]
+    #[case("impl Greeter for Foo {}", true)]
+    #[case("impl crate::Greeter for Foo {}", true)]
+    #[case("impl self::Greeter for Foo {}", true)]
+    #[case("mod inner { impl super::Greeter for Foo {} }", true)]
+    #[case("mod inner { impl Greeter for Foo {} }", false)]
+    #[case("impl other::Greeter for Foo {}", false)]
+    #[case("impl ::other::Greeter for Foo {}", false)]
+    #[case("impl Display for Foo {}", false)]
+    fn trait_defaults_follow_the_trait_path(#[case] imp: &str, #[case] resolved: bool) {
+        let src = format!("trait Greeter {{ fn greet(&self) {{}} }}\n{}", imp);
+        let opts = AnalyzeOptions {
+            resolve_trait_defaults: true,
+            ..AnalyzeOptions::default()
+        };
+        let tree =
+            ComplexityTree::from_source_with_options(String::from("test"), &src, &opts).unwrap();
+
+        let greets = tree
+            .functions()
+            .filter(|(path, _)| path.contains(" for Foo > Method: greet"))
+            .count();
+        assert_eq!(resolved as usize, greets);
+    }
+
+    #[test]
+    fn trait_defaults_in_modules_resolve() {
+        let src = "
+            mod a { pub trait Greeter { fn greet(&self) {} } }
+            impl a::Greeter for Foo {}
+        ";
+        let opts = AnalyzeOptions {
+            resolve_trait_defaults: true,
+            ..AnalyzeOptions::default()
+        };
+        let imp = &tree_with_options(src, &opts).children[1];
+
+        assert_eq!(1, imp.children.len());
+        assert!(imp.children[0].public);
+    }
+
+    #[rstest]
+    #[case("trait", false)]
+    #[case("pub trait", true)]
+    fn trait_defaults_are_as_public_as_the_trait(#[case] decl: &str, #[case] public: bool) {
+        let src = format!(
+            "{} Greeter {{ fn greet(&self) {{}} }}\nimpl Greeter for Foo {{}}",
+            decl
+        );
+        let opts = AnalyzeOptions {
+            resolve_trait_defaults: true,
+            ..AnalyzeOptions::default()
+        };
+        let imp = &tree_with_options(&src, &opts).children[1];
+
+        assert_eq!(
+            vec![public],
+            imp.children.iter().map(|m| m.public).collect::<Vec<_>>()
+        );
+    }
+
+    #[rstest]
+    #[case("#[cfg(test)] mod tests {}", true)]
+    #[case("#[cfg(all(test, feature = \"slow\"))] mod tests {}", true)]
+    #[case("#[cfg(all(unix, all(test)))] mod tests {}", true)]
+    #[case("#[cfg_attr(feature = \"slow\", cfg(test))] mod tests {}", true)]
+    #[case("mod tests { #![cfg(test)] }", true)]
+    #[case("#[cfg(not(test))] mod tests {}", false)]
+    #[case("#[cfg(any(test, unix))] mod tests {}", false)]
+    #[case("#[cfg_attr(test, allow(dead_code))] mod tests {}", false)]
+    #[case("mod tests {}", false)]
+    fn detects_test_only_modules(#[case] src: &str, #[case] test_only: bool) {
+        let ast: syn::ItemMod = syn::parse_str(src).unwrap();
+        assert_eq!(test_only, is_test_only(&ast.attrs));
+    }
+
+    #[test]
+    fn from_source_matches_generate() {
+        let path = "tests/fixtures/clean.rs";
+        let src = std::fs::read_to_string(path).unwrap();
+
+        assert_eq!(
+            ComplexityTree::generate(String::from(path)).unwrap(),
+            ComplexityTree::from_source(String::from(path), &src).unwrap()
+        );
+    }
+
+    #[test]
+    fn from_source_names_the_root() {
+        let tree = ComplexityTree::from_source(String::from("<stdin>"), "fn f() { a?; }").unwrap();
+        assert_eq!("<stdin>", tree.root.name);
+        assert_eq!(1, tree.root.children[0].complexity);
+    }
+
+    #[test]
+    fn errors_can_be_matched_on() {
+        let missing = ComplexityTree::generate(String::from("tests/fixtures/missing.rs"));
+        assert_eq!(ParseErrorKind::Io, missing.unwrap_err().kind);
+
+        let invalid = ComplexityTree::from_source_with_options(
+            String::from("lib.rs"),
+            "fn f( {",
+            &AnalyzeOptions::default(),
+        );
+        assert_eq!(ParseErrorKind::Syntax, invalid.unwrap_err().kind);
+    }
+
+    #[test]
+    fn generate_all_captures_each_file_result() {
+        let patterns = vec![String::from("tests/fixtures/scan/*.rs")];
+        let results = ComplexityTree::generate_all(&patterns, &ScanOptions::default());
+
+        assert_eq!(2, results.len());
+        assert_eq!("tests/fixtures/scan/bad.rs", results[0].0);
+        assert!(results[0].1.is_err());
+        assert_eq!("tests/fixtures/scan/good.rs", results[1].0);
+        assert_eq!(
+            1,
+            results[1].1.as_ref().unwrap().root.children[0].complexity
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! JSON output of the whole tree of every file, for other tooling to consume.
+use crate::parsers::rust_parser::ComplexityTree;
+use crate::stats::Summary;
+
+/// Render the trees as a single JSON array, one `{"root": ..}` object per
+/// file with every node nested under its parent's `children`.
+pub fn render(trees: &[ComplexityTree]) -> String {
+    let mut out = serde_json::to_string(trees).expect("trees always serialize");
+    out += "\n";
+
+    out
+}
+
+/// Render the statistics of `--summary` as a single JSON object, those over
+/// every tree under `summary` and those of every directory under
+/// `directories`, by directory.
+pub fn summary(trees: &[ComplexityTree]) -> String {
+    let summary = serde_json::json!({
+        "summary": Summary::from_trees(trees),
+        "directories": Summary::by_directory(trees),
+    });
+    let mut out = serde_json::to_string(&summary).expect("summaries always serialize");
+    out += "\n";
+
+    out
+}
+
+/// Read back a report produced by `render`.
+pub fn parse(src: &str) -> serde_json::Result<Vec<ComplexityTree>> {
+    serde_json::from_str(src)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, render, summary};
+    use crate::parsers::rust_parser::{ComplexityNode, ComplexityNodeKind, ComplexityTree};
+    use insta::assert_snapshot;
+    use serde_json::Value;
+
+    #[test]
+    fn keeps_the_nesting_of_the_tree() {
+        let mut imp = ComplexityNode::new(String::from("Foo"), ComplexityNodeKind::Impl);
+        imp.add_child(
+            ComplexityNode::new(String::from("bar"), ComplexityNodeKind::Method).with_complexity(3),
+        );
+        let mut root = ComplexityNode::new(String::from("src/lib.rs"), ComplexityNodeKind::File);
+        root.add_child(imp);
+
+        let out: Value = serde_json::from_str(&render(&[ComplexityTree { root }])).unwrap();
+
+        let root = &out[0]["root"];
+        assert_eq!("src/lib.rs", root["name"]);
+        assert_eq!("File", root["kind"]);
+        let imp = &root["children"][0];
+        assert_eq!("Impl", imp["kind"]);
+        assert_eq!("bar", imp["children"][0]["name"]);
+        assert_eq!("Method", imp["children"][0]["kind"]);
+        assert_eq!(3, imp["children"][0]["complexity"]);
+        assert_eq!(0, imp["children"][0]["children"].as_array().unwrap().len());
+    }
+
+    #[test]
+    fn fixture_snapshot() {
+        let trees: Vec<ComplexityTree> =
+            ["tests/fixtures/clean.rs", "tests/fixtures/over_budget.rs"]
+                .iter()
+                .map(|file| ComplexityTree::generate(file.to_string()).unwrap())
+                .collect();
+
+        let out = render(&trees);
+        assert_eq!(out, render(&trees));
+        assert_snapshot!(out);
+    }
+
+    #[test]
+    fn render_round_trips_through_parse() {
+        let tree = ComplexityTree::generate(String::from("tests/fixtures/clean.rs")).unwrap();
+        let mut expected = ComplexityTree {
+            root: tree.root.clone(),
+        };
+        for child in expected.root.children.iter_mut() {
+            child.deepest = None;
+        }
+
+        assert_eq!(vec![expected], parse(&render(&[tree])).unwrap());
+    }
+
+    #[test]
+    fn summary_is_structured() {
+        let mut root = ComplexityNode::new(String::from("src/lib.rs"), ComplexityNodeKind::File);
+        root.add_child(
+            ComplexityNode::new(String::from("bar"), ComplexityNodeKind::Fn)
+                .with_complexity(3)
+                .with_position(7, 0),
+        );
+
+        let out: Value = serde_json::from_str(&summary(&[ComplexityTree { root }])).unwrap();
+
+        let total = &out["summary"];
+        assert_eq!(1, total["count"]);
+        assert_eq!(3, total["total"]);
+        assert_eq!(3.0, total["median"]);
+        assert_eq!(3, total["p90"]);
+        assert_eq!("src/lib.rs", total["most_complex"]["file"]);
+        assert_eq!("Fn: bar", total["most_complex"]["path"]);
+        assert_eq!(7, total["most_complex"]["line"]);
+        assert_eq!(total, &out["directories"]["src"]);
+    }
+}
@@ -0,0 +1,108 @@
+//! GitHub flavored Markdown table of the functions of every tree, most
+//! complex first, for bots posting reports as pull request comments.
+use crate::parsers::rust_parser::ComplexityTree;
+use crate::report::rows;
+use std::cmp::Reverse;
+
+/// Render the functions at least `min_complexity` complex as a table,
+/// followed by the total and the max of every function, in the table or
+/// not. Hidden functions count in neither, as in the stats. Ties stay in
+/// source order.
+pub fn render(trees: &[ComplexityTree], min_complexity: usize) -> String {
+    let all: Vec<_> = trees.iter().flat_map(rows).collect();
+    let total = all.iter().fold(0, |total: usize, row| {
+        total.saturating_add(row.node.complexity)
+    });
+    let max = all.iter().map(|row| row.node.complexity).max().unwrap_or(0);
+
+    let mut shown: Vec<_> = all
+        .into_iter()
+        .filter(|row| row.node.complexity >= min_complexity)
+        .collect();
+    shown.sort_by_key(|row| Reverse(row.node.complexity));
+
+    let mut out = String::from("| File | Function | Complexity |\n| --- | --- | ---: |\n");
+    for row in shown {
+        out += &format!(
+            "| {} | `{}` | {} |\n",
+            escape(row.file),
+            escape(&row.path),
+            row.node.complexity
+        );
+    }
+    out += &format!("\nTotal complexity: {}, max: {}\n", total, max);
+
+    out
+}
+
+/// `|` ends a cell even inside a code span.
+fn escape(cell: &str) -> String {
+    cell.replace('|', "\\|")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::parsers::rust_parser::{ComplexityNodeKind, ComplexityTree};
+    use crate::report::fixtures::{file, imp, node};
+
+    fn trees() -> Vec<ComplexityTree> {
+        vec![
+            file(
+                "src/lib.rs",
+                vec![
+                    node("baz", ComplexityNodeKind::Fn, 3),
+                    imp("Foo<T>", vec![node("bar", ComplexityNodeKind::Method, 12)]),
+                ],
+            ),
+            file(
+                "src|main.rs",
+                vec![
+                    node("main", ComplexityNodeKind::Fn, 1),
+                    node("run", ComplexityNodeKind::Fn, 3),
+                ],
+            ),
+        ]
+    }
+
+    #[test]
+    fn lists_functions_most_complex_first() {
+        let should_be = "| File | Function | Complexity |\n\
+                         | --- | --- | ---: |\n\
+                         | src/lib.rs | `Impl: Foo<T> > Method: bar` | 12 |\n\
+                         | src/lib.rs | `Fn: baz` | 3 |\n\
+                         | src\\|main.rs | `Fn: run` | 3 |\n\
+                         | src\\|main.rs | `Fn: main` | 1 |\n\
+                         \n\
+                         Total complexity: 19, max: 12\n";
+
+        assert_eq!(should_be, render(&trees(), 0));
+    }
+
+    #[test]
+    fn min_complexity_shortens_the_table_but_not_the_summary() {
+        let should_be = "| File | Function | Complexity |\n\
+                         | --- | --- | ---: |\n\
+                         | src/lib.rs | `Impl: Foo<T> > Method: bar` | 12 |\n\
+                         \n\
+                         Total complexity: 19, max: 12\n";
+
+        assert_eq!(should_be, render(&trees(), 4));
+    }
+
+    #[test]
+    fn hidden_functions_are_left_out_of_the_summary() {
+        let mut trees = trees();
+        trees[1].root.children[0].public = true;
+        for tree in trees.iter_mut() {
+            tree.hide_private();
+        }
+        let should_be = "| File | Function | Complexity |\n\
+                         | --- | --- | ---: |\n\
+                         | src\\|main.rs | `Fn: main` | 1 |\n\
+                         \n\
+                         Total complexity: 1, max: 1\n";
+
+        assert_eq!(should_be, render(&trees, 0));
+    }
+}
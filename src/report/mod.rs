@@ -0,0 +1,108 @@
+//! Module for rendering complexity trees in the supported output formats.
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod markdown;
+#[cfg(feature = "serde")]
+pub mod ndjson;
+pub mod plain_paths;
+#[cfg(feature = "serde")]
+pub mod review;
+#[cfg(feature = "serde")]
+pub mod sarif;
+pub mod tabular;
+pub mod text;
+#[cfg(feature = "serde")]
+pub mod trend;
+
+use crate::parsers::rust_parser::{ComplexityNode, ComplexityTree};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Format {
+    Text,
+    Csv,
+    Tsv,
+    Ndjson,
+    Json,
+    Review,
+    Sarif,
+    PlainPaths,
+    /// The control-flow graph of each file, for Delphi only.
+    Dot,
+    /// A table of the functions, for pull request comments.
+    Markdown,
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Format, String> {
+        match s {
+            "text" => Ok(Format::Text),
+            "csv" => Ok(Format::Csv),
+            "tsv" => Ok(Format::Tsv),
+            "ndjson" => Ok(Format::Ndjson),
+            "json" => Ok(Format::Json),
+            "review" => Ok(Format::Review),
+            "sarif" => Ok(Format::Sarif),
+            "plain-paths" => Ok(Format::PlainPaths),
+            "dot" => Ok(Format::Dot),
+            "markdown" => Ok(Format::Markdown),
+            other => Err(format!("unknown format: {}", other)),
+        }
+    }
+}
+
+/// Budget of the formats listing over budget functions when no
+/// `--threshold` is given, McCabe's classic limit.
+pub const DEFAULT_BUDGET: usize = 10;
+
+/// A function or method of a tree, along with its `a > b > c` breadcrumb.
+pub(crate) struct Row<'a> {
+    pub(crate) file: &'a str,
+    pub(crate) path: String,
+    pub(crate) node: &'a ComplexityNode,
+}
+
+pub(crate) fn rows(tree: &ComplexityTree) -> Vec<Row<'_>> {
+    tree.functions()
+        .map(|(path, node)| Row {
+            file: &tree.root.name,
+            path,
+            node,
+        })
+        .collect()
+}
+
+/// The functions of every tree that are more complex than `budget`.
+pub(crate) fn violations(trees: &[ComplexityTree], budget: usize) -> Vec<Row<'_>> {
+    trees
+        .iter()
+        .flat_map(rows)
+        .filter(|row| row.node.complexity > budget)
+        .collect()
+}
+
+/// Hand built trees for the tests of the reports.
+#[cfg(test)]
+pub(crate) mod fixtures {
+    use crate::parsers::rust_parser::{ComplexityNode, ComplexityNodeKind, ComplexityTree};
+
+    pub(crate) fn node(name: &str, kind: ComplexityNodeKind, complexity: usize) -> ComplexityNode {
+        ComplexityNode::new(name.to_string(), kind).with_complexity(complexity)
+    }
+
+    /// `impl name` with `methods` in it.
+    pub(crate) fn imp(name: &str, methods: Vec<ComplexityNode>) -> ComplexityNode {
+        let mut imp = node(name, ComplexityNodeKind::Impl, 0);
+        imp.children = methods;
+        imp
+    }
+
+    /// The tree of the file `name` with `children` at its top level.
+    pub(crate) fn file(name: &str, children: Vec<ComplexityNode>) -> ComplexityTree {
+        let mut root = node(name, ComplexityNodeKind::File, 0);
+        root.children = children;
+        ComplexityTree { root }
+    }
+}
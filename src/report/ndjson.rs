@@ -0,0 +1,96 @@
+//! Newline delimited JSON output, one object per function or method.
+//!
+//! The output is byte for byte reproducible so it can be committed and
+//! snapshot tested: functions come in source order and keys in the field
+//! order of `Record`. Don't put hash maps in here.
+use crate::parsers::rust_parser::ComplexityTree;
+use crate::report::rows;
+use serde::{Deserialize, Serialize};
+
+/// A single line of an NDJSON report.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+pub struct Record {
+    pub file: String,
+    pub path: String,
+    pub kind: String,
+    pub name: String,
+    pub complexity: usize,
+}
+
+/// Render the functions of every tree, one JSON object per line.
+pub fn render(trees: &[ComplexityTree]) -> String {
+    let mut out = String::new();
+
+    for tree in trees {
+        for row in rows(tree) {
+            let record = Record {
+                file: row.file.to_string(),
+                path: row.path,
+                kind: row.node.kind.to_string(),
+                name: row.node.name.clone(),
+                complexity: row.node.complexity,
+            };
+            out += &serde_json::to_string(&record).expect("records always serialize");
+            out += "\n";
+        }
+    }
+
+    out
+}
+
+/// Read back a report produced by `render`, skipping blank lines.
+pub fn parse(src: &str) -> serde_json::Result<Vec<Record>> {
+    src.lines()
+        .filter(|line| !line.trim().is_empty())
+        .map(serde_json::from_str)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, render, Record};
+    use crate::parsers::rust_parser::{ComplexityNode, ComplexityNodeKind, ComplexityTree};
+    use insta::assert_snapshot;
+
+    #[test]
+    fn render_round_trips_through_parse() {
+        let mut root = ComplexityNode::new(String::from("src/lib.rs"), ComplexityNodeKind::File);
+        root.add_child(
+            ComplexityNode::new(String::from("baz"), ComplexityNodeKind::Fn).with_complexity(2),
+        );
+        let out = render(&[ComplexityTree { root }]);
+
+        assert_eq!(
+            "{\"file\":\"src/lib.rs\",\"path\":\"Fn: baz\",\"kind\":\"Fn\",\"name\":\"baz\",\"complexity\":2}\n",
+            out
+        );
+        assert_eq!(
+            vec![Record {
+                file: String::from("src/lib.rs"),
+                path: String::from("Fn: baz"),
+                kind: String::from("Fn"),
+                name: String::from("baz"),
+                complexity: 2,
+            }],
+            parse(&out).unwrap()
+        );
+    }
+
+    #[test]
+    fn fixture_snapshot() {
+        let trees: Vec<ComplexityTree> =
+            ["tests/fixtures/clean.rs", "tests/fixtures/over_budget.rs"]
+                .iter()
+                .map(|file| ComplexityTree::generate(file.to_string()).unwrap())
+                .collect();
+
+        let out = render(&trees);
+        assert_eq!(out, render(&trees));
+        assert_snapshot!(out);
+    }
+
+    #[test]
+    fn parse_rejects_malformed_lines() {
+        assert!(parse("{\"file\": 1}\n").is_err());
+    }
+}
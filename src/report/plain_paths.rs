@@ -0,0 +1,37 @@
+//! `path:line:col: complexity N` lines for over budget functions, in the
+//! shape of compiler errors so editors (e.g. vim's quickfix) can jump to them.
+use crate::parsers::rust_parser::ComplexityTree;
+use crate::report::violations;
+
+pub fn render(trees: &[ComplexityTree], budget: usize) -> String {
+    let mut out = String::new();
+
+    for row in violations(trees, budget) {
+        out += &format!(
+            "{}:{}:{}: complexity {}\n",
+            row.file, row.node.line, row.node.column, row.node.complexity
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::render;
+    use crate::analyzer::AnalyzeOptions;
+    use crate::parsers::rust_parser::ComplexityTree;
+
+    #[test]
+    fn lists_violations_like_compiler_errors() {
+        let src = "fn small() {}\n\nimpl Foo {\n    fn big() { a?; b?; c?; }\n}\n";
+        let tree = ComplexityTree::from_source_with_options(
+            String::from("src/lib.rs"),
+            src,
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+
+        assert_eq!("src/lib.rs:4:8: complexity 3\n", render(&[tree], 2));
+    }
+}
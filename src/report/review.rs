@@ -0,0 +1,106 @@
+//! Suggestions for code review bots, one JSON object per line for every
+//! function over the complexity budget.
+use crate::parsers::rust_parser::ComplexityTree;
+use crate::report::violations;
+use serde::Serialize;
+
+/// A comment to leave on the line a function starts on.
+#[derive(Debug, PartialEq, Serialize)]
+pub struct Suggestion {
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+pub fn suggestions(trees: &[ComplexityTree], budget: usize) -> Vec<Suggestion> {
+    violations(trees, budget)
+        .into_iter()
+        .map(|row| {
+            let node = row.node;
+            let mut message = format!(
+                "function {} has complexity {} (budget {}); ",
+                node.name, node.complexity, budget
+            );
+            match &node.deepest {
+                Some(deepest) if deepest.level > 1 => {
+                    message += &format!(
+                        "consider extracting the nested {} at line {}",
+                        deepest.construct, deepest.line
+                    );
+                }
+                _ => message += "consider splitting it into smaller functions",
+            }
+
+            Suggestion {
+                file: row.file.to_string(),
+                line: node.line,
+                message,
+            }
+        })
+        .collect()
+}
+
+/// Render the suggestions of every tree, one JSON object per line.
+pub fn render(trees: &[ComplexityTree], budget: usize) -> String {
+    let mut out = String::new();
+
+    for suggestion in suggestions(trees, budget) {
+        out += &serde_json::to_string(&suggestion).expect("suggestions always serialize");
+        out += "\n";
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, suggestions, Suggestion};
+    use crate::analyzer::AnalyzeOptions;
+    use crate::parsers::rust_parser::ComplexityTree;
+
+    fn tree(src: &str) -> ComplexityTree {
+        ComplexityTree::from_source_with_options(
+            String::from("src/lib.rs"),
+            src,
+            &AnalyzeOptions::default(),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn suggests_extracting_the_deepest_construct() {
+        let src = "fn small() {}
+
+fn big() {
+    if a && b {
+        match c {
+            _ if d => {}
+            _ => {}
+        }
+    }
+}";
+
+        assert_eq!(
+            vec![Suggestion {
+                file: String::from("src/lib.rs"),
+                line: 3,
+                message: String::from(
+                    "function big has complexity 4 (budget 2); \
+                     consider extracting the nested match at line 5"
+                ),
+            }],
+            suggestions(&[tree(src)], 2)
+        );
+    }
+
+    #[test]
+    fn flat_functions_are_asked_to_split() {
+        let out = render(&[tree("fn f() { a?; b?; c?; }")], 2);
+
+        assert_eq!(
+            "{\"file\":\"src/lib.rs\",\"line\":1,\"message\":\"function f has complexity 3 \
+             (budget 2); consider splitting it into smaller functions\"}\n",
+            out
+        );
+    }
+}
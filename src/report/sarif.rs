@@ -0,0 +1,101 @@
+//! A SARIF 2.1.0 log of the functions over the complexity budget, for code
+//! scanning UIs such as GitHub's.
+use crate::parsers::rust_parser::ComplexityTree;
+use crate::report::violations;
+use serde_json::{json, Value};
+
+/// The single rule every result is reported under.
+pub const RULE_ID: &str = "cyclomatic-complexity";
+
+pub fn log(trees: &[ComplexityTree], budget: usize) -> Value {
+    let results: Vec<Value> = violations(trees, budget)
+        .into_iter()
+        .map(|row| {
+            let node = row.node;
+            let mut location = json!({
+                "physicalLocation": {
+                    "artifactLocation": { "uri": row.file },
+                },
+            });
+            // SARIF regions are 1-based, so unknown positions are left out
+            if node.line > 0 {
+                location["physicalLocation"]["region"] = json!({
+                    "startLine": node.line,
+                    "startColumn": node.column.max(1),
+                });
+            }
+
+            json!({
+                "ruleId": RULE_ID,
+                "level": "warning",
+                "message": {
+                    "text": format!(
+                        "{} has complexity {} (budget {})",
+                        row.path, node.complexity, budget
+                    ),
+                },
+                "locations": [location],
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "rules": [{
+                        "id": RULE_ID,
+                        "shortDescription": {
+                            "text": "Function is more complex than the budget",
+                        },
+                    }],
+                },
+            },
+            "results": results,
+        }],
+    })
+}
+
+/// Render the log of every tree as a single JSON document.
+pub fn render(trees: &[ComplexityTree], budget: usize) -> String {
+    let mut out = serde_json::to_string_pretty(&log(trees, budget)).expect("logs always serialize");
+    out += "\n";
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{log, RULE_ID};
+    use crate::analyzer::AnalyzeOptions;
+    use crate::parsers::rust_parser::ComplexityTree;
+
+    #[test]
+    fn over_budget_functions_become_results() {
+        let src = "fn small() {}\n\nimpl Foo {\n    fn big() { a?; b?; c?; }\n}\n";
+        let tree = ComplexityTree::from_source_with_options(
+            String::from("src/lib.rs"),
+            src,
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+
+        let log = log(&[tree], 2);
+        assert_eq!("2.1.0", log["version"]);
+        let results = log["runs"][0]["results"].as_array().unwrap();
+        assert_eq!(1, results.len());
+        assert_eq!(RULE_ID, results[0]["ruleId"]);
+        assert_eq!(
+            "Impl: Foo > Method: big has complexity 3 (budget 2)",
+            results[0]["message"]["text"]
+        );
+        let location = &results[0]["locations"][0]["physicalLocation"];
+        assert_eq!("src/lib.rs", location["artifactLocation"]["uri"]);
+        assert_eq!(4, location["region"]["startLine"]);
+        assert_eq!(8, location["region"]["startColumn"]);
+    }
+}
@@ -0,0 +1,224 @@
+//! Tabular (CSV/TSV) output, one row per function or method.
+use crate::parsers::rust_parser::ComplexityTree;
+use crate::report::{rows, Row};
+use std::str::FromStr;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Column {
+    File,
+    Path,
+    Kind,
+    Name,
+    Complexity,
+    Line,
+    Loc,
+    Density,
+}
+
+impl FromStr for Column {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Column, String> {
+        match s {
+            "file" => Ok(Column::File),
+            "path" => Ok(Column::Path),
+            "kind" => Ok(Column::Kind),
+            "name" => Ok(Column::Name),
+            "complexity" => Ok(Column::Complexity),
+            "line" => Ok(Column::Line),
+            "loc" => Ok(Column::Loc),
+            "density" => Ok(Column::Density),
+            other => Err(format!("unknown column: {}", other)),
+        }
+    }
+}
+
+impl Column {
+    fn header(&self) -> &'static str {
+        match self {
+            Column::File => "file",
+            Column::Path => "path",
+            Column::Kind => "kind",
+            Column::Name => "name",
+            Column::Complexity => "complexity",
+            Column::Line => "line",
+            Column::Loc => "loc",
+            Column::Density => "density",
+        }
+    }
+}
+
+/// Columns emitted when none are requested.
+pub const DEFAULT_COLUMNS: [Column; 5] = [
+    Column::File,
+    Column::Path,
+    Column::Kind,
+    Column::Name,
+    Column::Complexity,
+];
+
+impl<'a> Row<'a> {
+    fn project(&self, columns: &[Column]) -> Vec<String> {
+        columns
+            .iter()
+            .map(|column| match column {
+                Column::File => self.file.to_string(),
+                Column::Path => self.path.clone(),
+                Column::Kind => self.node.kind.to_string(),
+                Column::Name => self.node.name.clone(),
+                Column::Complexity => self.node.complexity.to_string(),
+                Column::Line => self.node.line.to_string(),
+                Column::Loc => self.node.loc.to_string(),
+                Column::Density => format!("{:.2}", self.node.density()),
+            })
+            .collect()
+    }
+}
+
+/// Render the functions of every tree as comma-separated values, with a header.
+pub fn csv(trees: &[ComplexityTree], columns: &[Column]) -> String {
+    render(trees, columns, ",", escape_csv)
+}
+
+/// Render the functions of every tree as tab-separated values, with a header.
+pub fn tsv(trees: &[ComplexityTree], columns: &[Column]) -> String {
+    render(trees, columns, "\t", escape_tsv)
+}
+
+fn render(
+    trees: &[ComplexityTree],
+    columns: &[Column],
+    separator: &str,
+    escape: fn(&str) -> String,
+) -> String {
+    let mut lines: Vec<String> = vec![columns
+        .iter()
+        .map(Column::header)
+        .collect::<Vec<&str>>()
+        .join(separator)];
+
+    for tree in trees {
+        for row in rows(tree) {
+            let fields: Vec<String> = row
+                .project(columns)
+                .iter()
+                .map(|field| escape(field))
+                .collect();
+            lines.push(fields.join(separator));
+        }
+    }
+
+    lines.join("\n") + "\n"
+}
+
+/// `field` quoted when it holds a comma, a quote or a line break.
+pub(crate) fn escape_csv(field: &str) -> String {
+    if field.contains(&[',', '"', '\n', '\r'][..]) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+fn escape_tsv(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{csv, escape_csv, escape_tsv, tsv, Column, DEFAULT_COLUMNS};
+    use crate::analyzer::AnalyzeOptions;
+    use crate::parsers::rust_parser::{ComplexityNodeKind, ComplexityTree};
+    use crate::report::fixtures::{file, imp, node};
+
+    fn tree() -> ComplexityTree {
+        file(
+            "src/lib.rs",
+            vec![
+                node("baz", ComplexityNodeKind::Fn, 1),
+                imp("Foo", vec![node("bar", ComplexityNodeKind::Method, 3)]),
+            ],
+        )
+    }
+
+    #[test]
+    fn csv_uses_default_columns() {
+        let should_be = "file,path,kind,name,complexity\n\
+                         src/lib.rs,Fn: baz,Fn,baz,1\n\
+                         src/lib.rs,Impl: Foo > Method: bar,Method,bar,3\n";
+
+        assert_eq!(should_be, csv(&[tree()], &DEFAULT_COLUMNS));
+    }
+
+    #[test]
+    fn tsv_respects_column_selection_and_order() {
+        let columns = vec![Column::Complexity, Column::Name, Column::File];
+        let should_be = "complexity\tname\tfile\n\
+                         1\tbaz\tsrc/lib.rs\n\
+                         3\tbar\tsrc/lib.rs\n";
+
+        assert_eq!(should_be, tsv(&[tree()], &columns));
+    }
+
+    #[test]
+    fn csv_quotes_special_fields() {
+        assert_eq!("plain", escape_csv("plain"));
+        assert_eq!(
+            "\"Display for Foo<K, V>\"",
+            escape_csv("Display for Foo<K, V>")
+        );
+        assert_eq!("\"say \"\"hi\"\"\"", escape_csv("say \"hi\""));
+    }
+
+    #[test]
+    fn csv_quotes_paths_of_generic_impls() {
+        let tree = ComplexityTree::from_source_with_options(
+            String::from("src/lib.rs"),
+            "impl<K, V> From<(K, V)> for Map<K, V> { fn from(pair: (K, V)) -> Self { pair.into()? } }",
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+
+        let should_be = "file,path,kind,name,complexity\n\
+                         src/lib.rs,\"Impl: From<(K, V)> for Map<K, V> > Method: from\",Method,from,1\n";
+        assert_eq!(should_be, csv(&[tree], &DEFAULT_COLUMNS));
+    }
+
+    #[test]
+    fn line_column_holds_the_start_line() {
+        let tree = ComplexityTree::from_source_with_options(
+            String::from("src/lib.rs"),
+            "fn a() {}\n\nfn b() {}\n",
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+
+        let columns = vec![Column::Name, Column::Line];
+        assert_eq!("name,line\na,1\nb,3\n", csv(&[tree], &columns));
+    }
+
+    #[test]
+    fn loc_and_density_columns_measure_the_body() {
+        let tree = ComplexityTree::from_source_with_options(
+            String::from("src/lib.rs"),
+            "fn a() { if a {} }\n\nfn b() {\n    if a {}\n    if b {}\n    if c {}\n}\n",
+            &AnalyzeOptions::default(),
+        )
+        .unwrap();
+
+        let columns = vec![Column::Name, Column::Loc, Column::Density];
+        assert_eq!(
+            "name,loc,density\na,1,1.00\nb,5,0.60\n",
+            csv(&[tree], &columns)
+        );
+    }
+
+    #[test]
+    fn tsv_escapes_tabs_and_newlines() {
+        assert_eq!("a\\tb\\nc\\\\d", escape_tsv("a\tb\nc\\d"));
+    }
+}
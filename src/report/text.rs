@@ -0,0 +1,497 @@
+//! Human readable output, one `[path] Complexity => N` line per node
+//! followed by the total of the file.
+use crate::parsers::rust_parser::{ComplexityNode, ComplexityTree};
+use crate::stats::Summary;
+use std::cmp::Reverse;
+use std::fmt;
+use std::str::FromStr;
+
+/// Order of the functions of a `--sort`ed text report.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Sort {
+    /// Most complex first.
+    Complexity,
+    /// Alphabetical by function name.
+    Name,
+    /// Most complexity per line first, see `ComplexityNode::density`.
+    Density,
+}
+
+impl FromStr for Sort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Sort, String> {
+        match s {
+            "complexity" => Ok(Sort::Complexity),
+            "name" => Ok(Sort::Name),
+            "density" => Ok(Sort::Density),
+            other => Err(format!("unknown sort order: {}", other)),
+        }
+    }
+}
+
+/// How to lay out the text output.
+#[derive(Debug, Clone)]
+pub struct TextOptions {
+    /// Pad paths and right-justify complexities so the numbers of a file
+    /// line up in a single column.
+    pub align: bool,
+    /// List only functions and methods, in this order instead of source
+    /// order. Ties stay in source order.
+    pub sort: Option<Sort>,
+    /// End the names of nodes with a known position in `:line`, e.g.
+    /// `[Fn: sign:5]`.
+    pub line_numbers: bool,
+    /// Leave out functions less complex than this, along with impls and
+    /// traits left without any function to show.
+    pub min_complexity: usize,
+    /// Color the complexities of functions by how severe they are, with ANSI
+    /// escapes.
+    pub color: Option<Severity>,
+}
+
+impl Default for TextOptions {
+    fn default() -> TextOptions {
+        TextOptions {
+            align: true,
+            sort: None,
+            line_numbers: false,
+            min_complexity: 0,
+            color: None,
+        }
+    }
+}
+
+/// Complexities up to `low` are green, up to `high` yellow and red beyond.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Severity {
+    pub low: usize,
+    pub high: usize,
+}
+
+impl Default for Severity {
+    fn default() -> Severity {
+        Severity { low: 5, high: 10 }
+    }
+}
+
+/// `low,high`, e.g. `5,10`.
+impl FromStr for Severity {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Severity, String> {
+        let thresholds: Vec<&str> = s.split(',').map(str::trim).collect();
+        let (low, high) = match thresholds.as_slice() {
+            [low, high] => (low.parse::<usize>(), high.parse::<usize>()),
+            _ => return Err(format!("expected two thresholds like 5,10: {}", s)),
+        };
+        match (low, high) {
+            (Ok(low), Ok(high)) if low <= high => Ok(Severity { low, high }),
+            (Ok(_), Ok(_)) => Err(format!("the first threshold is above the second: {}", s)),
+            _ => Err(format!("thresholds must be whole numbers: {}", s)),
+        }
+    }
+}
+
+impl Severity {
+    fn escape(&self, complexity: usize) -> &'static str {
+        if complexity <= self.low {
+            GREEN
+        } else if complexity <= self.high {
+            YELLOW
+        } else {
+            RED
+        }
+    }
+}
+
+const GREEN: &str = "\x1b[32m";
+const YELLOW: &str = "\x1b[33m";
+const RED: &str = "\x1b[31m";
+const RESET: &str = "\x1b[0m";
+
+/// Render a tree as text.
+///
+/// Every node gets a line, parents before their children. Functions and
+/// methods show their own complexity, impls and traits the total of what's
+/// nested in them. The file's line and total are always printed, even when
+/// `min_complexity` leaves nothing else.
+pub fn render(tree: &ComplexityTree, opts: &TextOptions) -> String {
+    let mut nodes: Vec<(String, &ComplexityNode)> = match opts.sort {
+        Some(_) => tree
+            .functions()
+            .filter(|(_, node)| node.complexity >= opts.min_complexity)
+            .collect(),
+        None => tree
+            .nodes()
+            .filter(|(_, node)| shown(node, opts.min_complexity))
+            .collect(),
+    };
+    match opts.sort {
+        Some(Sort::Complexity) => nodes.sort_by_key(|(_, node)| Reverse(node.complexity)),
+        Some(Sort::Name) => nodes.sort_by(|(_, a), (_, b)| a.name.cmp(&b.name)),
+        Some(Sort::Density) => nodes.sort_by(|(_, a), (_, b)| b.density().total_cmp(&a.density())),
+        None => {}
+    }
+    let lines: Vec<(String, usize, Option<&str>)> = nodes
+        .into_iter()
+        .map(|(path, node)| {
+            let label = if opts.line_numbers && node.line > 0 {
+                format!("[{}:{}]", path, node.line)
+            } else {
+                format!("[{}]", path)
+            };
+            // impls and traits show totals, which the thresholds aren't for
+            let escape = opts
+                .color
+                .filter(|_| node.kind.is_function())
+                .map(|severity| severity.escape(node.complexity));
+            (label, complexity(node), escape)
+        })
+        .collect();
+
+    let (label_width, number_width) = if opts.align {
+        (
+            lines
+                .iter()
+                .map(|(label, _, _)| label.chars().count())
+                .max()
+                .unwrap_or(0),
+            lines
+                .iter()
+                .map(|(_, complexity, _)| complexity.to_string().len())
+                .max()
+                .unwrap_or(0),
+        )
+    } else {
+        (0, 0)
+    };
+
+    let mut out = format!("File: {}\n", tree.root.name);
+    for (label, complexity, escape) in lines.iter() {
+        let number = format!("{:>number_width$}", complexity, number_width = number_width);
+        let number = match escape {
+            Some(escape) => format!("{}{}{}", escape, number, RESET),
+            None => number,
+        };
+        out += &format!(
+            "{:<label_width$} Complexity => {}\n",
+            label,
+            number,
+            label_width = label_width,
+        );
+    }
+    out += &format!("Total complexity: {}\n", tree.root.total());
+    out += "\n";
+
+    out
+}
+
+/// Render the summary block of `--summary`: one per directory when the
+/// trees span several, then one over every tree.
+pub fn summary(trees: &[ComplexityTree]) -> String {
+    let mut out = String::new();
+    let directories = Summary::by_directory(trees);
+    if directories.len() > 1 {
+        for (directory, summary) in directories {
+            out += &format!("Summary of {}:\n{}\n", directory, summary);
+        }
+    }
+    out += &format!("Summary:\n{}", Summary::from_trees(trees));
+
+    out
+}
+
+/// The text report with the default options, so `println!("{}", tree)`
+/// prints what the binary does without any flags.
+impl fmt::Display for ComplexityTree {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&render(self, &TextOptions::default()))
+    }
+}
+
+/// Whether `node` is, or contains, a function at least `min_complexity`
+/// complex.
+fn shown(node: &ComplexityNode, min_complexity: usize) -> bool {
+    (node.kind.is_function() && !node.hidden && node.complexity >= min_complexity)
+        || node
+            .children
+            .iter()
+            .any(|child| shown(child, min_complexity))
+}
+
+fn complexity(node: &ComplexityNode) -> usize {
+    if node.kind.is_function() {
+        node.complexity
+    } else {
+        node.total()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render, summary, Severity, Sort, TextOptions};
+    use crate::parsers::rust_parser::{ComplexityNodeKind, ComplexityTree};
+    use crate::report::fixtures::{file, imp, node};
+    use rstest::rstest;
+
+    fn tree() -> ComplexityTree {
+        file(
+            "src/lib.rs",
+            vec![
+                node("baz", ComplexityNodeKind::Fn, 3),
+                imp("Foo", vec![node("bar", ComplexityNodeKind::Method, 12)]),
+                node("alpha", ComplexityNodeKind::Fn, 3),
+            ],
+        )
+    }
+
+    #[test]
+    fn aligns_complexity_column() {
+        let should_be = "File: src/lib.rs\n\
+                         [Fn: baz]                 Complexity =>  3\n\
+                         [Impl: Foo]               Complexity => 12\n\
+                         [Impl: Foo > Method: bar] Complexity => 12\n\
+                         [Fn: alpha]               Complexity =>  3\n\
+                         Total complexity: 18\n\
+                         \n";
+
+        assert_eq!(should_be, render(&tree(), &TextOptions::default()));
+    }
+
+    #[test]
+    fn display_renders_with_the_default_options() {
+        assert_eq!(render(&tree(), &TextOptions::default()), tree().to_string());
+    }
+
+    #[test]
+    fn summary_of_one_directory_has_a_single_block() {
+        let should_be = "Summary:\n\
+                         Functions: 3\n\
+                         Total complexity: 18\n\
+                         Average complexity: 6.00\n\
+                         Median complexity: 3\n\
+                         90th percentile: 12\n\
+                         Most complex: [Impl: Foo > Method: bar] at src/lib.rs, complexity 12\n";
+
+        assert_eq!(should_be, summary(&[tree()]));
+    }
+
+    #[test]
+    fn summary_of_several_directories_has_a_block_per_directory() {
+        let main = file(
+            "src/bin/main.rs",
+            vec![node("main", ComplexityNodeKind::Fn, 2)],
+        );
+        let out = summary(&[tree(), main]);
+
+        let headings: Vec<&str> = out
+            .lines()
+            .filter(|line| line.starts_with("Summary"))
+            .collect();
+        assert_eq!(
+            vec!["Summary of src:", "Summary of src/bin:", "Summary:"],
+            headings
+        );
+        assert!(out.contains("Summary of src/bin:\nFunctions: 1\nTotal complexity: 2\n"));
+        assert!(
+            out.ends_with("Most complex: [Impl: Foo > Method: bar] at src/lib.rs, complexity 12\n")
+        );
+    }
+
+    #[test]
+    fn no_align_keeps_plain_lines() {
+        let should_be = "File: src/lib.rs\n\
+                         [Fn: baz] Complexity => 3\n\
+                         [Impl: Foo] Complexity => 12\n\
+                         [Impl: Foo > Method: bar] Complexity => 12\n\
+                         [Fn: alpha] Complexity => 3\n\
+                         Total complexity: 18\n\
+                         \n";
+
+        assert_eq!(
+            should_be,
+            render(
+                &tree(),
+                &TextOptions {
+                    align: false,
+                    ..TextOptions::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn sort_by_complexity_lists_functions_most_complex_first() {
+        let should_be = "File: src/lib.rs\n\
+                         [Impl: Foo > Method: bar] Complexity => 12\n\
+                         [Fn: baz] Complexity => 3\n\
+                         [Fn: alpha] Complexity => 3\n\
+                         Total complexity: 18\n\
+                         \n";
+
+        assert_eq!(
+            should_be,
+            render(
+                &tree(),
+                &TextOptions {
+                    align: false,
+                    sort: Some(Sort::Complexity),
+                    ..TextOptions::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn sort_by_name_lists_functions_alphabetically() {
+        let should_be = "File: src/lib.rs\n\
+                         [Fn: alpha] Complexity => 3\n\
+                         [Impl: Foo > Method: bar] Complexity => 12\n\
+                         [Fn: baz] Complexity => 3\n\
+                         Total complexity: 18\n\
+                         \n";
+
+        assert_eq!(
+            should_be,
+            render(
+                &tree(),
+                &TextOptions {
+                    align: false,
+                    sort: Some(Sort::Name),
+                    ..TextOptions::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn sort_by_density_lists_functions_with_most_complexity_per_line_first() {
+        let tree = file(
+            "src/lib.rs",
+            vec![
+                node("long", ComplexityNodeKind::Fn, 8).with_loc(200),
+                node("short", ComplexityNodeKind::Fn, 8).with_loc(8),
+                node("unknown", ComplexityNodeKind::Fn, 2),
+            ],
+        );
+
+        let should_be = "File: src/lib.rs\n\
+                         [Fn: unknown] Complexity => 2\n\
+                         [Fn: short] Complexity => 8\n\
+                         [Fn: long] Complexity => 8\n\
+                         Total complexity: 18\n\
+                         \n";
+
+        assert_eq!(
+            should_be,
+            render(
+                &tree,
+                &TextOptions {
+                    align: false,
+                    sort: Some(Sort::Density),
+                    ..TextOptions::default()
+                }
+            )
+        );
+    }
+
+    #[test]
+    fn color_marks_functions_by_severity() {
+        let tree = file(
+            "src/lib.rs",
+            vec![
+                imp("Foo", vec![node("bar", ComplexityNodeKind::Method, 12)]),
+                node("baz", ComplexityNodeKind::Fn, 5),
+                node("qux", ComplexityNodeKind::Fn, 6),
+            ],
+        );
+
+        let should_be = "File: src/lib.rs\n\
+                         [Impl: Foo]               Complexity => 12\n\
+                         [Impl: Foo > Method: bar] Complexity => \x1b[31m12\x1b[0m\n\
+                         [Fn: baz]                 Complexity => \x1b[32m 5\x1b[0m\n\
+                         [Fn: qux]                 Complexity => \x1b[33m 6\x1b[0m\n\
+                         Total complexity: 23\n\
+                         \n";
+
+        assert_eq!(
+            should_be,
+            render(
+                &tree,
+                &TextOptions {
+                    color: Some(Severity::default()),
+                    ..TextOptions::default()
+                }
+            )
+        );
+    }
+
+    #[rstest]
+    #[case("5,10", Ok(Severity { low: 5, high: 10 }))]
+    #[case(" 3 , 3 ", Ok(Severity { low: 3, high: 3 }))]
+    #[case(
+        "10,5",
+        Err(String::from("the first threshold is above the second: 10,5"))
+    )]
+    #[case("5", Err(String::from("expected two thresholds like 5,10: 5")))]
+    #[case(
+        "five,10",
+        Err(String::from("thresholds must be whole numbers: five,10"))
+    )]
+    fn severity_parses_two_thresholds(#[case] s: &str, #[case] expected: Result<Severity, String>) {
+        assert_eq!(expected, s.parse::<Severity>());
+    }
+
+    #[test]
+    fn line_numbers_follow_the_names_of_positioned_nodes() {
+        let tree = file(
+            "src/lib.rs",
+            vec![imp(
+                "Foo",
+                vec![node("bar", ComplexityNodeKind::Method, 2).with_position(4, 8)],
+            )],
+        );
+
+        let should_be = "File: src/lib.rs\n\
+                         [Impl: Foo] Complexity => 2\n\
+                         [Impl: Foo > Method: bar:4] Complexity => 2\n\
+                         Total complexity: 2\n\
+                         \n";
+
+        assert_eq!(
+            should_be,
+            render(
+                &tree,
+                &TextOptions {
+                    align: false,
+                    line_numbers: true,
+                    ..TextOptions::default()
+                }
+            )
+        );
+    }
+
+    #[rstest]
+    #[case(
+        4,
+        "File: src/lib.rs\n\
+         [Impl: Foo] Complexity => 12\n\
+         [Impl: Foo > Method: bar] Complexity => 12\n\
+         Total complexity: 18\n\
+         \n"
+    )]
+    #[case(13, "File: src/lib.rs\nTotal complexity: 18\n\n")]
+    fn min_complexity_prunes_simple_functions(
+        #[case] min_complexity: usize,
+        #[case] should_be: &str,
+    ) {
+        let opts = TextOptions {
+            align: false,
+            min_complexity,
+            ..TextOptions::default()
+        };
+
+        assert_eq!(should_be, render(&tree(), &opts));
+    }
+}
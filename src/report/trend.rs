@@ -0,0 +1,63 @@
+//! Aggregation of several NDJSON reports into a complexity time series.
+use crate::report::ndjson::Record;
+use crate::report::tabular::escape_csv;
+use crate::stats::Stats;
+
+/// Render one CSV row per run with the number of functions and the total,
+/// max and average complexity, keyed by the run's label, which is quoted as
+/// in `tabular::csv`.
+pub fn aggregate(runs: &[(String, Vec<Record>)]) -> String {
+    let mut out = String::from("label,functions,total,max,average\n");
+
+    for (label, records) in runs {
+        let values: Vec<usize> = records.iter().map(|record| record.complexity).collect();
+        let stats = Stats::from_values(&values);
+        out += &format!(
+            "{},{},{},{},{:.2}\n",
+            escape_csv(label),
+            stats.count,
+            stats.total,
+            stats.max,
+            stats.average
+        );
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::aggregate;
+    use crate::report::ndjson::parse;
+
+    #[test]
+    fn aggregates_each_run() {
+        let first = "{\"file\":\"a.rs\",\"path\":\"Fn: f\",\"kind\":\"Fn\",\"name\":\"f\",\"complexity\":2}\n\
+                     {\"file\":\"a.rs\",\"path\":\"Fn: g\",\"kind\":\"Fn\",\"name\":\"g\",\"complexity\":3}\n";
+        let second = "{\"file\":\"a.rs\",\"path\":\"Fn: f\",\"kind\":\"Fn\",\"name\":\"f\",\"complexity\":2}\n\
+                      {\"file\":\"a.rs\",\"path\":\"Fn: g\",\"kind\":\"Fn\",\"name\":\"g\",\"complexity\":6}\n\
+                      {\"file\":\"b.rs\",\"path\":\"Fn: h\",\"kind\":\"Fn\",\"name\":\"h\",\"complexity\":0}\n";
+        let runs = vec![
+            (String::from("abc123"), parse(first).unwrap()),
+            (String::from("def456"), parse(second).unwrap()),
+        ];
+
+        let should_be = "label,functions,total,max,average\n\
+                         abc123,2,5,3,2.50\n\
+                         def456,3,8,6,2.67\n";
+        assert_eq!(should_be, aggregate(&runs));
+    }
+
+    #[test]
+    fn labels_are_quoted() {
+        let runs = vec![
+            (String::from("v1.0, rc"), vec![]),
+            (String::from("say \"hi\""), vec![]),
+        ];
+
+        let should_be = "label,functions,total,max,average\n\
+                         \"v1.0, rc\",0,0,0,0.00\n\
+                         \"say \"\"hi\"\"\",0,0,0,0.00\n";
+        assert_eq!(should_be, aggregate(&runs));
+    }
+}
@@ -0,0 +1,269 @@
+//! Module for summarizing complexity values.
+use crate::parsers::rust_parser::ComplexityTree;
+use crate::report::{rows, Row};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+
+/// Aggregate figures over a set of complexities.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Stats {
+    pub count: usize,
+    pub total: usize,
+    pub max: usize,
+    pub average: f64,
+    /// The middle value, or the mean of the two middle ones.
+    pub median: f64,
+    /// The smallest value at least 90% of the values are at most.
+    pub p90: usize,
+}
+
+impl Stats {
+    pub fn from_values(values: &[usize]) -> Stats {
+        let count = values.len();
+        let total = values.iter().fold(0usize, |acc, v| acc.saturating_add(*v));
+        let max = values.iter().copied().max().unwrap_or(0);
+        let average = if count == 0 {
+            0.0
+        } else {
+            total as f64 / count as f64
+        };
+
+        let mut sorted = values.to_vec();
+        sorted.sort_unstable();
+        let median = match count {
+            0 => 0.0,
+            _ if count % 2 == 1 => sorted[count / 2] as f64,
+            _ => (sorted[count / 2 - 1] as f64 + sorted[count / 2] as f64) / 2.0,
+        };
+        // nearest rank, the value 90% of the way up the sorted list
+        let p90 = match count {
+            0 => 0,
+            _ => sorted[(9 * count).div_ceil(10) - 1],
+        };
+
+        Stats {
+            count,
+            total,
+            max,
+            average,
+            median,
+            p90,
+        }
+    }
+}
+
+/// Statistics over the functions and methods of a set of trees, e.g. of a
+/// whole crate.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Summary {
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub stats: Stats,
+    /// The first of the most complex functions, `None` without functions.
+    pub most_complex: Option<Hotspot>,
+}
+
+/// Where a function is, and how complex.
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Hotspot {
+    pub file: String,
+    /// The `Kind: name > Kind: name` breadcrumb of the function.
+    pub path: String,
+    /// 0 for nodes without a position.
+    pub line: usize,
+    pub complexity: usize,
+}
+
+impl Summary {
+    pub fn from_trees(trees: &[ComplexityTree]) -> Summary {
+        Summary::from_rows(trees.iter().flat_map(rows).collect())
+    }
+
+    /// A summary of the files of every directory, by directory. Files
+    /// without one, such as `<stdin>`, count as in `.`.
+    pub fn by_directory(trees: &[ComplexityTree]) -> BTreeMap<String, Summary> {
+        let mut directories: BTreeMap<String, Vec<Row<'_>>> = BTreeMap::new();
+        for tree in trees {
+            let directory = match Path::new(&tree.root.name).parent() {
+                Some(parent) if !parent.as_os_str().is_empty() => {
+                    parent.to_string_lossy().into_owned()
+                }
+                _ => String::from("."),
+            };
+            directories.entry(directory).or_default().extend(rows(tree));
+        }
+
+        directories
+            .into_iter()
+            .map(|(directory, rows)| (directory, Summary::from_rows(rows)))
+            .collect()
+    }
+
+    fn from_rows(rows: Vec<Row<'_>>) -> Summary {
+        let values: Vec<usize> = rows.iter().map(|row| row.node.complexity).collect();
+
+        let mut most_complex: Option<Hotspot> = None;
+        for row in rows {
+            if most_complex
+                .as_ref()
+                .is_some_and(|hotspot| hotspot.complexity >= row.node.complexity)
+            {
+                continue;
+            }
+            most_complex = Some(Hotspot {
+                file: row.file.to_string(),
+                path: row.path,
+                line: row.node.line,
+                complexity: row.node.complexity,
+            });
+        }
+
+        Summary {
+            stats: Stats::from_values(&values),
+            most_complex,
+        }
+    }
+}
+
+impl fmt::Display for Summary {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Functions: {}", self.stats.count)?;
+        writeln!(f, "Total complexity: {}", self.stats.total)?;
+        writeln!(f, "Average complexity: {:.2}", self.stats.average)?;
+        writeln!(f, "Median complexity: {}", self.stats.median)?;
+        writeln!(f, "90th percentile: {}", self.stats.p90)?;
+        match &self.most_complex {
+            Some(hotspot) => {
+                let location = match hotspot.line {
+                    0 => hotspot.file.clone(),
+                    line => format!("{}:{}", hotspot.file, line),
+                };
+                writeln!(
+                    f,
+                    "Most complex: [{}] at {}, complexity {}",
+                    hotspot.path, location, hotspot.complexity
+                )
+            }
+            None => writeln!(f, "Most complex: none"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Hotspot, Stats, Summary};
+    use crate::parsers::rust_parser::{ComplexityNodeKind, ComplexityTree};
+    use crate::report::fixtures::{file, node};
+    use rstest::rstest;
+
+    #[test]
+    fn stats_over_values() {
+        let stats = Stats::from_values(&[1, 4, 2, 1]);
+
+        assert_eq!(4, stats.count);
+        assert_eq!(8, stats.total);
+        assert_eq!(4, stats.max);
+        assert_eq!(2.0, stats.average);
+        assert_eq!(1.5, stats.median);
+        assert_eq!(4, stats.p90);
+    }
+
+    #[test]
+    fn stats_over_nothing() {
+        let stats = Stats::from_values(&[]);
+
+        assert_eq!(0, stats.count);
+        assert_eq!(0, stats.max);
+        assert_eq!(0.0, stats.average);
+        assert_eq!(0.0, stats.median);
+        assert_eq!(0, stats.p90);
+    }
+
+    #[rstest]
+    #[case(vec![7], 7.0, 7)]
+    #[case(vec![3, 1, 2], 2.0, 3)]
+    #[case(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10], 5.5, 9)]
+    #[case(vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10, 11], 6.0, 10)]
+    fn median_and_p90(#[case] values: Vec<usize>, #[case] median: f64, #[case] p90: usize) {
+        let stats = Stats::from_values(&values);
+
+        assert_eq!(median, stats.median);
+        assert_eq!(p90, stats.p90);
+    }
+
+    fn tree(name: &str, functions: &[(&str, usize, usize)]) -> ComplexityTree {
+        let functions = functions
+            .iter()
+            .map(|(name, complexity, line)| {
+                node(name, ComplexityNodeKind::Fn, *complexity).with_position(*line, 4)
+            })
+            .collect();
+        file(name, functions)
+    }
+
+    fn trees() -> Vec<ComplexityTree> {
+        vec![
+            tree("src/lib.rs", &[("a", 1, 1), ("b", 9, 5)]),
+            tree("src/report/text.rs", &[("c", 9, 3), ("d", 2, 8)]),
+            tree("src/report/json.rs", &[("e", 0, 2)]),
+        ]
+    }
+
+    #[test]
+    fn summary_finds_the_first_most_complex_function() {
+        let summary = Summary::from_trees(&trees());
+
+        assert_eq!(Stats::from_values(&[1, 9, 9, 2, 0]), summary.stats);
+        assert_eq!(
+            Some(Hotspot {
+                file: String::from("src/lib.rs"),
+                path: String::from("Fn: b"),
+                line: 5,
+                complexity: 9,
+            }),
+            summary.most_complex
+        );
+    }
+
+    #[test]
+    fn summaries_by_directory() {
+        let mut trees = trees();
+        trees.push(tree("<stdin>", &[("f", 4, 1)]));
+        let directories = Summary::by_directory(&trees);
+
+        let counts: Vec<(&str, usize, usize)> = directories
+            .iter()
+            .map(|(directory, summary)| {
+                (directory.as_str(), summary.stats.count, summary.stats.total)
+            })
+            .collect();
+        assert_eq!(
+            vec![(".", 1, 4), ("src", 2, 10), ("src/report", 3, 11)],
+            counts
+        );
+    }
+
+    #[test]
+    fn display_lists_every_figure() {
+        let should_be = "Functions: 5\n\
+                         Total complexity: 21\n\
+                         Average complexity: 4.20\n\
+                         Median complexity: 2\n\
+                         90th percentile: 9\n\
+                         Most complex: [Fn: b] at src/lib.rs:5, complexity 9\n";
+
+        assert_eq!(should_be, Summary::from_trees(&trees()).to_string());
+        assert_eq!(
+            "Functions: 0\n\
+             Total complexity: 0\n\
+             Average complexity: 0.00\n\
+             Median complexity: 0\n\
+             90th percentile: 0\n\
+             Most complex: none\n",
+            Summary::from_trees(&[]).to_string()
+        );
+    }
+}
@@ -1,16 +1,48 @@
+use std::any::Any;
+use std::error::Error;
+use std::fmt;
+use std::panic::{self, AssertUnwindSafe};
 use std::sync::{mpsc, Arc, Mutex};
 use std::thread;
 
 type Job = Box<dyn FnOnce() + Send + 'static>;
 
+/// `log::$level!` with the `log` feature; otherwise the message is dropped,
+/// its arguments still count as used.
+macro_rules! pool_log {
+    ($level:ident, $($arg:tt)*) => {
+        #[cfg(feature = "log")]
+        log::$level!($($arg)*);
+        #[cfg(not(feature = "log"))]
+        let _ = format_args!($($arg)*);
+    };
+}
+
 enum Message {
     NewJob(Job),
     Terminate,
 }
 
+#[derive(Debug, PartialEq)]
+pub enum PoolError {
+    /// The pool was shut down and no longer accepts jobs.
+    ShutDown,
+}
+
+impl fmt::Display for PoolError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PoolError::ShutDown => write!(f, "thread pool is shut down"),
+        }
+    }
+}
+
+impl Error for PoolError {}
+
 pub struct ThreadPool {
     sender: mpsc::Sender<Message>,
     workers: Vec<Worker>,
+    shut_down: bool,
 }
 
 impl ThreadPool {
@@ -24,33 +56,85 @@ impl ThreadPool {
             .map(|id| Worker::new(id, Arc::clone(&receiver)))
             .collect();
 
-        ThreadPool { sender, workers }
+        ThreadPool {
+            sender,
+            workers,
+            shut_down: false,
+        }
     }
 
-    pub fn execute<F>(&mut self, f: F)
+    pub fn execute<F>(&mut self, f: F) -> Result<(), PoolError>
     where
         F: FnOnce() + Send + 'static,
     {
+        if self.shut_down {
+            return Err(PoolError::ShutDown);
+        }
+
         let job = Message::NewJob(Box::new(f));
-        self.sender.send(job).unwrap();
+        self.sender.send(job).map_err(|_| PoolError::ShutDown)
     }
-}
 
-impl Drop for ThreadPool {
-    fn drop(&mut self) {
-        println!("Sending terminate message to all workers");
+    /// Like `execute`, but the value `f` returns is sent to the returned
+    /// receiver once the job ran. The receiver is disconnected without a
+    /// value if the job panics.
+    pub fn execute_with_result<T, F>(&mut self, f: F) -> Result<mpsc::Receiver<T>, PoolError>
+    where
+        T: Send + 'static,
+        F: FnOnce() -> T + Send + 'static,
+    {
+        let (sender, receiver) = mpsc::channel();
+        self.execute(move || {
+            // the caller may have dropped the receiver, not wanting the value
+            let _ = sender.send(f());
+        })?;
+
+        Ok(receiver)
+    }
+
+    /// Wait for the queued jobs to finish and stop every worker. Later calls
+    /// to `execute` fail with `PoolError::ShutDown`; shutting down twice is a
+    /// no-op.
+    pub fn shutdown(&mut self) {
+        if self.shut_down {
+            return;
+        }
+        self.shut_down = true;
+
+        pool_log!(debug, "Sending terminate message to all workers");
         for _ in &self.workers {
             self.sender.send(Message::Terminate).unwrap();
         }
 
-        println!("Shutting Down all workers");
+        pool_log!(debug, "Shutting Down all workers");
         for worker in &mut self.workers {
-            println!("Shutting down worker with id: {}", worker.id);
+            pool_log!(debug, "Shutting down worker with id: {}", worker.id);
             if let Some(thread) = worker.thread.take() {
                 thread.join().unwrap();
             }
         }
     }
+
+    /// Shut the pool down, returning once every queued job finished.
+    pub fn join(mut self) {
+        self.shutdown();
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn panic_message(payload: &Box<dyn Any + Send>) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
 }
 
 struct Worker {
@@ -65,11 +149,20 @@ impl Worker {
 
             match message {
                 Message::NewJob(job) => {
-                    println!("Worker {} got a new job. Executing!", id);
-                    job();
+                    pool_log!(trace, "Worker {} got a new job. Executing!", id);
+                    // a panicking job (e.g. syn choking on a file) must not
+                    // take the worker, and the jobs queued after it, down
+                    if let Err(payload) = panic::catch_unwind(AssertUnwindSafe(job)) {
+                        pool_log!(
+                            error,
+                            "Worker {} job panicked: {}",
+                            id,
+                            panic_message(&payload)
+                        );
+                    }
                 }
                 Message::Terminate => {
-                    println!("Worker {} was told to terminate. Terminating!", id);
+                    pool_log!(debug, "Worker {} was told to terminate. Terminating!", id);
                     break;
                 }
             }
@@ -81,3 +174,79 @@ impl Worker {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{PoolError, ThreadPool};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn execute_runs_jobs() {
+        let mut pool = ThreadPool::new(2);
+        let (sender, receiver) = mpsc::channel();
+
+        pool.execute(move || sender.send(42).unwrap()).unwrap();
+
+        assert_eq!(42, receiver.recv().unwrap());
+    }
+
+    #[test]
+    fn execute_with_result_returns_every_result() {
+        let mut pool = ThreadPool::new(4);
+
+        let receivers: Vec<mpsc::Receiver<usize>> = (0..32)
+            .map(|i| pool.execute_with_result(move || i * i).unwrap())
+            .collect();
+        let results: Vec<usize> = receivers
+            .into_iter()
+            .map(|receiver| receiver.recv().unwrap())
+            .collect();
+
+        assert_eq!((0..32).map(|i| i * i).collect::<Vec<usize>>(), results);
+    }
+
+    #[test]
+    fn panicking_jobs_leave_the_worker_alive() {
+        let mut pool = ThreadPool::new(1);
+
+        let panicked = pool
+            .execute_with_result(|| -> usize { panic!("bad file") })
+            .unwrap();
+        let next = pool.execute_with_result(|| 42).unwrap();
+
+        assert!(panicked.recv().is_err());
+        assert_eq!(42, next.recv().unwrap());
+        pool.join();
+    }
+
+    #[test]
+    fn join_waits_for_queued_jobs() {
+        let mut pool = ThreadPool::new(2);
+        let done = Arc::new(AtomicUsize::new(0));
+
+        for _ in 0..16 {
+            let done = Arc::clone(&done);
+            pool.execute(move || {
+                thread::sleep(Duration::from_millis(1));
+                done.fetch_add(1, Ordering::SeqCst);
+            })
+            .unwrap();
+        }
+        pool.join();
+
+        assert_eq!(16, done.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn execute_after_shutdown_is_an_error() {
+        let mut pool = ThreadPool::new(2);
+        pool.shutdown();
+        pool.shutdown();
+
+        assert_eq!(Err(PoolError::ShutDown), pool.execute(|| {}));
+        assert!(pool.execute_with_result(|| 1).is_err());
+    }
+}
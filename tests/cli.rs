@@ -0,0 +1,664 @@
+use assert_cmd::Command;
+use predicates::prelude::*;
+
+const CLEAN: &str = "tests/fixtures/clean.rs";
+const OVER_BUDGET: &str = "tests/fixtures/over_budget.rs";
+
+fn cli() -> Command {
+    Command::cargo_bin("main").unwrap()
+}
+
+#[test]
+fn reports_every_function() {
+    cli()
+        .args(["--file", CLEAN])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(format!("File: {}", CLEAN)))
+        .stdout(predicate::str::contains("[Fn: add]  Complexity => 0"))
+        .stdout(predicate::str::contains("[Fn: sign] Complexity => 1"))
+        .stdout(predicate::str::contains("Total complexity: 1\n"));
+}
+
+#[test]
+fn no_align_prints_plain_lines() {
+    cli()
+        .args(["--file", CLEAN, "--no-align"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[Fn: add] Complexity => 0"));
+}
+
+#[test]
+fn threshold_passes_for_clean_file() {
+    cli()
+        .args(["--file", CLEAN, "--threshold", "5"])
+        .assert()
+        .success();
+}
+
+#[test]
+fn threshold_fails_for_over_budget_file() {
+    cli()
+        .args(["--file", OVER_BUDGET, "--threshold", "4"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("[Fn: classify] Complexity => 5"));
+}
+
+#[test]
+fn missing_file_is_an_error() {
+    cli()
+        .args(["--file", "tests/fixtures/missing.rs"])
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr(predicate::str::starts_with(
+            "error: could not read tests/fixtures/missing.rs: ",
+        ));
+}
+
+#[test]
+fn syntax_errors_point_at_the_problem() {
+    cli()
+        .args(["--file", "tests/fixtures/scan/bad.rs", CLEAN])
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr(predicate::str::contains(
+            "error: could not parse tests/fixtures/scan/bad.rs: ",
+        ))
+        .stderr(predicate::str::contains(" at 1:9\n"));
+}
+
+#[test]
+fn tsv_with_selected_columns() {
+    cli()
+        .args([
+            "--file",
+            CLEAN,
+            "--format",
+            "tsv",
+            "--columns",
+            "complexity,name",
+        ])
+        .assert()
+        .success()
+        .stdout("complexity\tname\n0\tadd\n1\tsign\n");
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn trend_aggregates_ndjson_reports() {
+    let output = cli()
+        .args(["--file", CLEAN, "--format", "ndjson"])
+        .output()
+        .unwrap();
+    let report = std::env::temp_dir().join(format!("clean-{}.ndjson", std::process::id()));
+    std::fs::write(&report, output.stdout).unwrap();
+
+    Command::cargo_bin("trend")
+        .unwrap()
+        .args(["--label", "abc123"])
+        .arg(&report)
+        .assert()
+        .success()
+        .stdout("label,functions,total,max,average\nabc123,2,1,1,0.50\n");
+
+    std::fs::remove_file(report).unwrap();
+}
+
+fn git(repo: &std::path::Path, args: &[&str]) {
+    let status = std::process::Command::new("git")
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(args)
+        .current_dir(repo)
+        .output()
+        .unwrap()
+        .status;
+    assert!(status.success(), "git {:?} failed", args);
+}
+
+#[test]
+fn fail_on_regression_compares_against_merge_base() {
+    let repo = std::env::temp_dir().join(format!("regression-{}", std::process::id()));
+    std::fs::create_dir_all(&repo).unwrap();
+    git(&repo, &["init", "-q", "-b", "main"]);
+    std::fs::write(repo.join("lib.rs"), "fn f(a: bool) { if a {} }\n").unwrap();
+    git(&repo, &["add", "lib.rs"]);
+    git(&repo, &["commit", "-q", "-m", "base"]);
+    git(&repo, &["checkout", "-q", "-b", "feature"]);
+
+    cli()
+        .current_dir(&repo)
+        .args(["--file", "lib.rs", "--fail-on-regression"])
+        .assert()
+        .success();
+
+    std::fs::write(repo.join("lib.rs"), "fn f(a: bool) { if a {} if !a {} }\n").unwrap();
+    cli()
+        .current_dir(&repo)
+        .args(["--file", "lib.rs", "--fail-on-regression"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("Regression").not())
+        .stderr(predicate::str::contains(
+            "Regression: lib.rs: [Fn: f] Complexity 1 => 2",
+        ));
+
+    std::fs::remove_dir_all(repo).unwrap();
+}
+
+#[test]
+fn fail_on_regression_finds_files_from_anywhere_in_the_repo() {
+    let repo = std::env::temp_dir().join(format!("regression-paths-{}", std::process::id()));
+    std::fs::create_dir_all(repo.join("src")).unwrap();
+    std::fs::create_dir_all(repo.join("docs")).unwrap();
+    git(&repo, &["init", "-q", "-b", "main"]);
+    std::fs::write(repo.join("src/lib.rs"), "fn f(a: bool) { if a {} }\n").unwrap();
+    git(&repo, &["add", "src/lib.rs"]);
+    git(&repo, &["commit", "-q", "-m", "base"]);
+    git(&repo, &["checkout", "-q", "-b", "feature"]);
+    std::fs::write(
+        repo.join("src/lib.rs"),
+        "fn f(a: bool) { if a {} if !a {} }\n",
+    )
+    .unwrap();
+
+    let absolute = repo.join("src/lib.rs");
+    for file in ["../src/lib.rs", absolute.to_str().unwrap()] {
+        cli()
+            .current_dir(repo.join("docs"))
+            .args(["--file", file, "--fail-on-regression"])
+            .assert()
+            .code(1)
+            .stderr(predicate::str::contains("[Fn: f] Complexity 1 => 2"));
+    }
+
+    std::fs::remove_dir_all(repo).unwrap();
+}
+
+#[test]
+fn fail_on_regression_rejects_files_outside_the_repo() {
+    let repo = std::env::temp_dir().join(format!("regression-outside-{}", std::process::id()));
+    std::fs::create_dir_all(&repo).unwrap();
+    git(&repo, &["init", "-q", "-b", "main"]);
+    git(&repo, &["commit", "-q", "--allow-empty", "-m", "base"]);
+    let outside = std::fs::canonicalize(CLEAN).unwrap();
+
+    cli()
+        .current_dir(&repo)
+        .arg("--file")
+        .arg(&outside)
+        .arg("--fail-on-regression")
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains(format!(
+            "error: {} is outside the git repository",
+            outside.display()
+        )));
+
+    std::fs::remove_dir_all(repo).unwrap();
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn json_emits_the_whole_tree() {
+    cli()
+        .args(["--file", CLEAN, "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(format!(
+            "[{{\"root\":{{\"name\":\"{}\",\"kind\":\"File\"",
+            CLEAN
+        )))
+        .stdout(predicate::str::contains(
+            "\"name\":\"sign\",\"kind\":\"Fn\",\"complexity\":1",
+        ));
+}
+
+#[test]
+fn sort_lists_the_most_complex_functions_first() {
+    cli()
+        .args(["--file", CLEAN, "--sort", "complexity"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "File: {}\n[Fn: sign] Complexity => 1\n[Fn: add]  Complexity => 0\nTotal complexity: 1\n\n",
+            CLEAN
+        ));
+}
+
+#[test]
+fn csv_has_a_header_and_one_row_per_function() {
+    cli()
+        .args(["--file", CLEAN, "--format", "csv"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "file,path,kind,name,complexity\n{0},Fn: add,Fn,add,0\n{0},Fn: sign,Fn,sign,1\n",
+            CLEAN
+        ));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn sarif_reports_over_budget_functions() {
+    cli()
+        .args([
+            "--file",
+            OVER_BUDGET,
+            "--format",
+            "sarif",
+            "--threshold",
+            "4",
+        ])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("\"version\": \"2.1.0\""))
+        .stdout(predicate::str::contains(
+            "\"text\": \"Fn: classify has complexity 5 (budget 4)\"",
+        ));
+}
+
+#[test]
+fn line_numbers_follow_function_names() {
+    cli()
+        .args(["--file", CLEAN, "--line-numbers", "--no-align"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[Fn: add:1] Complexity => 0"))
+        .stdout(predicate::str::contains("[Fn: sign:5] Complexity => 1"));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn baseline_reports_functions_that_got_more_complex() {
+    let dir = std::env::temp_dir().join(format!("baseline-{}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let file = dir.join("lib.rs");
+    let file_arg = file.to_str().unwrap();
+
+    std::fs::write(&file, "fn f(a: bool) { if a {} }\n").unwrap();
+    let output = cli()
+        .args(["--file", file_arg, "--format", "json"])
+        .output()
+        .unwrap();
+    let baseline = dir.join("baseline.json");
+    std::fs::write(&baseline, output.stdout).unwrap();
+
+    cli()
+        .args(["--file", file_arg, "--baseline"])
+        .arg(&baseline)
+        .assert()
+        .success();
+
+    std::fs::write(
+        &file,
+        "fn f(a: bool) { if a {} if !a {} }\nfn g(a: bool) { if a {} }\n",
+    )
+    .unwrap();
+    cli()
+        .args(["--file", file_arg, "--threshold", "5", "--baseline"])
+        .arg(&baseline)
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("Regression").not())
+        .stderr(predicate::str::contains(format!(
+            "Regression: {}: [Fn: f] Complexity 1 => 2",
+            file_arg
+        )))
+        .stderr(predicate::str::contains("(new, budget 5)").not());
+
+    let output = cli()
+        .args(["--file", file_arg, "--format", "json", "--baseline"])
+        .arg(&baseline)
+        .output()
+        .unwrap();
+    assert_eq!(Some(1), output.status.code());
+    assert!(serde_json::from_slice::<serde_json::Value>(&output.stdout).is_ok());
+
+    std::fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn positional_files_get_a_section_each() {
+    cli()
+        .args([CLEAN, OVER_BUDGET, "--threshold", "4"])
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains(format!("File: {}", CLEAN)))
+        .stdout(predicate::str::contains(format!("File: {}", OVER_BUDGET)));
+}
+
+#[test]
+fn dir_analyzes_every_rust_file() {
+    cli()
+        .args(["--dir", "tests/fixtures/scan"])
+        .assert()
+        .code(2)
+        .stdout("")
+        .stderr(predicate::str::contains(
+            "error: could not parse tests/fixtures/scan/bad.rs",
+        ));
+}
+
+#[test]
+fn stdin_is_reported_as_its_own_file() {
+    cli()
+        .args(["-", "--no-align"])
+        .write_stdin("fn f(a: bool) { if a {} }\n")
+        .assert()
+        .success()
+        .stdout("File: <stdin>\n[Fn: f] Complexity => 1\nTotal complexity: 1\n\n");
+}
+
+#[test]
+fn min_complexity_hides_simple_functions() {
+    cli()
+        .args(["--file", CLEAN, "--min-complexity", "1"])
+        .assert()
+        .success()
+        .stdout(format!(
+            "File: {}\n[Fn: sign] Complexity => 1\nTotal complexity: 1\n\n",
+            CLEAN
+        ));
+}
+
+#[test]
+fn excluded_files_are_never_read() {
+    cli()
+        .args(["--dir", "tests/fixtures/scan", "--exclude", "**/bad.rs"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "File: tests/fixtures/scan/good.rs",
+        ))
+        .stdout(predicate::str::contains("bad.rs").not());
+}
+
+#[test]
+fn delphi_files_are_analyzed_by_extension() {
+    cli()
+        .args(["--file", "tests/fixtures/delphi/unit.pas"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[Fn: Sign]  Complexity => 1"))
+        .stdout(predicate::str::contains("[Fn: Count] Complexity => 1"));
+}
+
+#[test]
+fn unknown_extensions_need_a_lang() {
+    cli()
+        .args(["--file", "README.md"])
+        .assert()
+        .code(2)
+        .stderr(predicate::str::contains(
+        "error: could not analyze README.md: unknown extension, pass --lang rust or --lang delphi",
+    ));
+}
+
+#[test]
+fn dot_prints_the_graph_of_delphi_files() {
+    cli()
+        .args([
+            "--file",
+            "tests/fixtures/delphi/unit.pas",
+            "--format",
+            "dot",
+        ])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with("digraph {\n    1;\n"))
+        .stdout(predicate::str::contains("    1 -> 2;\n"));
+}
+
+#[test]
+fn markdown_tables_the_functions_most_complex_first() {
+    cli()
+        .args([
+            CLEAN,
+            OVER_BUDGET,
+            "--format",
+            "markdown",
+            "--min-complexity",
+            "1",
+        ])
+        .assert()
+        .success()
+        .stdout(format!(
+            "| File | Function | Complexity |\n\
+             | --- | --- | ---: |\n\
+             | {} | `Fn: classify` | 5 |\n\
+             | {} | `Fn: sign` | 1 |\n\
+             \n\
+             Total complexity: 6, max: 5\n",
+            OVER_BUDGET, CLEAN
+        ));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn config_file_of_the_working_directory_sets_options() {
+    cli()
+        .current_dir("tests/fixtures/config")
+        .arg("../clean.rs")
+        .assert()
+        .success()
+        .stdout("name,complexity\nadd,0\nsign,1\n");
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn flags_win_over_the_config_file() {
+    cli()
+        .current_dir("tests/fixtures/config")
+        .args(["../clean.rs", "--format", "text"])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[Fn: sign] Complexity => 1"));
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn malformed_config_files_are_an_error() {
+    cli()
+        .current_dir("tests/fixtures/config_broken")
+        .arg("../clean.rs")
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(predicate::str::contains(
+            "error: .complexity.toml: line 1: ",
+        ));
+}
+
+#[test]
+fn unknown_flags_are_an_error() {
+    cli()
+        .args([CLEAN, "--bogus"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr(predicate::str::contains("--bogus"));
+}
+
+#[test]
+fn help_is_not_an_error() {
+    cli()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("--threshold"));
+}
+
+#[test]
+fn truncated_functions_are_warned_about() {
+    cli()
+        .args(["--stdin", "--max-depth", "2"])
+        .write_stdin("fn deep() { if a { if b { if c {} } } }")
+        .assert()
+        .success()
+        .stderr(predicate::str::contains(
+            "warning: <stdin>: [Fn: deep] is nested deeper than --max-depth",
+        ));
+}
+
+#[test]
+fn quiet_leaves_out_warnings() {
+    cli()
+        .args(["--stdin", "--max-depth", "2", "-q"])
+        .write_stdin("fn deep() { if a { if b { if c {} } } }")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[Fn: deep]"))
+        .stderr("");
+}
+
+#[test]
+fn verbose_times_every_file_on_stderr() {
+    cli()
+        .args([
+            "--file",
+            CLEAN,
+            "--verbose",
+            "--format",
+            "csv",
+            "--columns",
+            "name",
+        ])
+        .assert()
+        .success()
+        .stdout("name\nadd\nsign\n")
+        .stderr(
+            predicate::str::is_match(r"^tests/fixtures/clean.rs: 2 nodes, 2 functions in \S+\n$")
+                .unwrap(),
+        );
+}
+
+#[test]
+fn color_always_marks_complexities() {
+    cli()
+        .args(["--file", CLEAN, "--color", "always"])
+        .env("NO_COLOR", "1")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(
+            "[Fn: sign] Complexity => \x1b[32m1\x1b[0m",
+        ));
+}
+
+#[test]
+fn pipes_get_no_color() {
+    cli()
+        .args(["--file", CLEAN])
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("\x1b[").not());
+}
+
+#[test]
+fn dot_refuses_rust_files() {
+    cli()
+        .args(["--file", CLEAN, "--format", "dot"])
+        .assert()
+        .code(1)
+        .stderr(predicate::str::contains("--format dot needs a Delphi file"));
+}
+
+#[test]
+fn constructs_can_be_left_out() {
+    cli()
+        .args(["--stdin", "--no-boolean-ops", "--no-try"])
+        .write_stdin("fn f() -> R { if a && b { g()?; } }")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("[Fn: f] Complexity => 1"));
+}
+
+#[test]
+fn cognitive_metric_weighs_nesting() {
+    cli()
+        .args(["--stdin", "--metric", "cognitive", "--threshold", "5"])
+        .write_stdin("fn f() { for x in y { if a { while b {} } } }")
+        .assert()
+        .code(1)
+        .stdout(predicate::str::contains("[Fn: f] Complexity => 6"));
+}
+
+#[test]
+fn summary_follows_the_text_report() {
+    cli()
+        .args([CLEAN, OVER_BUDGET, "--summary"])
+        .assert()
+        .success()
+        .stdout(predicate::str::ends_with(format!(
+            "Total complexity: 5\n\
+             \n\
+             Summary:\n\
+             Functions: 3\n\
+             Total complexity: 6\n\
+             Average complexity: 2.00\n\
+             Median complexity: 1\n\
+             90th percentile: 5\n\
+             Most complex: [Fn: classify] at {}:1, complexity 5\n",
+            OVER_BUDGET
+        )));
+}
+
+#[test]
+#[cfg(feature = "serde")]
+fn summary_replaces_the_trees_of_the_json_output() {
+    cli()
+        .args([CLEAN, OVER_BUDGET, "--summary", "--format", "json"])
+        .assert()
+        .success()
+        .stdout(predicate::str::starts_with(
+            "{\"directories\":{\"tests/fixtures\":{",
+        ))
+        .stdout(predicate::str::contains(
+            "\"summary\":{\"average\":2.0,\"count\":3,\"max\":5,\"median\":1.0",
+        ));
+}
+
+#[test]
+fn summary_needs_text_or_json() {
+    cli()
+        .args([CLEAN, "--summary", "--format", "csv"])
+        .assert()
+        .code(1)
+        .stdout("")
+        .stderr("error: --summary needs --format text or json\n");
+}
+
+#[test]
+fn only_public_keeps_private_functions_in_totals() {
+    cli()
+        .args(["--stdin", "--only-public", "--threshold", "1"])
+        .write_stdin(
+            "pub fn a() { if x {} } fn b() { if x {} if y {} }
+             impl S { pub fn c() { if x {} } fn d() { if x {} if y {} } }",
+        )
+        .assert()
+        .success()
+        .stdout(
+            "File: <stdin>\n\
+             [Fn: a]               Complexity => 1\n\
+             [Impl: S]             Complexity => 3\n\
+             [Impl: S > Method: c] Complexity => 1\n\
+             Total complexity: 6\n\
+             \n",
+        );
+}
+
+#[test]
+fn debug_graph_explains_every_delphi_routine() {
+    cli()
+        .args(["tests/fixtures/delphi/unit.pas", CLEAN, "--debug-graph"])
+        .assert()
+        .success()
+        .stderr(
+            "debug: tests/fixtures/delphi/unit.pas: [Fn: Sign] 6 - 6 + 1 + 1 = 2\n\
+             debug: tests/fixtures/delphi/unit.pas: [Fn: Count] 5 - 5 + 1 + 1 = 2\n",
+        );
+}
@@ -0,0 +1,11 @@
+fn add(a: i32, b: i32) -> i32 {
+    a + b
+}
+
+fn sign(a: i32) -> i32 {
+    if a < 0 {
+        -1
+    } else {
+        1
+    }
+}
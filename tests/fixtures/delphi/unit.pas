@@ -0,0 +1,26 @@
+unit Fixture;
+
+interface
+
+function Sign(Value: Integer): Integer;
+procedure Count(Limit: Integer);
+
+implementation
+
+function Sign(Value: Integer): Integer;
+begin
+  if Value < 0 then
+    Result := -1
+  else
+    Result := 1;
+end;
+
+procedure Count(Limit: Integer);
+var
+  I: Integer;
+begin
+  for I := 1 to Limit do
+    WriteLn(I);
+end;
+
+end.
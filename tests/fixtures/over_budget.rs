@@ -0,0 +1,16 @@
+fn classify(a: i32, b: i32) -> &'static str {
+    match a {
+        0 => {
+            if b > 0 {
+                "zero, positive"
+            } else if b < 0 {
+                "zero, negative"
+            } else {
+                "zero, zero"
+            }
+        }
+        1 => "one",
+        2 => "two",
+        _ => "many",
+    }
+}
@@ -0,0 +1,7 @@
+fn good(a: bool) -> i32 {
+    if a {
+        1
+    } else {
+        0
+    }
+}